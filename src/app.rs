@@ -1,15 +1,22 @@
 use dioxus::prelude::*;
-use crate::{state::app_state::AppState};
+use crate::{state::app_state::AppState, state::output::ValueBubble, state::project::StackSnippet, utils::clipboard};
+use crate::utils::{preview, sandbox};
+use tracing::warn;
 
 const FAVICON: Asset = asset!("/icons/favicon.ico");
 const MAIN_CSS: Asset = asset!("/assets/main.css");
 
 #[component]
 pub fn App() -> Element {
-    use_context_provider(|| Signal::new(AppState::default()));
+    use_context_provider(|| {
+        let mut state = AppState::default();
+        state.load_settings();
+        Signal::new(state)
+    });
 
     let mut app_state = use_context::<Signal<AppState>>();
-    
+    let mut cursor = use_signal(|| (0.0, 0.0));
+
     rsx! {
         document::Title { "tpl-dioxus" }
         document::Meta { charset: "UTF-8" }
@@ -21,9 +28,57 @@ pub fn App() -> Element {
 
         div {
             id: "app-container",
+            tabindex: "0",
+
+            onkeydown: move |e: KeyboardEvent| {
+                let modifiers = e.modifiers();
+                if !(modifiers.ctrl() || modifiers.meta()) {
+                    return;
+                }
+                match e.key() {
+                    Key::Character(c) if c.eq_ignore_ascii_case("c") => {
+                        let state = app_state.read();
+                        let Some(selected) = state.selected.clone() else {
+                            return;
+                        };
+                        let snippet = StackSnippet::new(state.copy_stack(&selected));
+                        match snippet.to_json() {
+                            Ok(text) => {
+                                if let Err(err) = clipboard::set_text(&text) {
+                                    warn!("Failed to copy blocks: {}", err);
+                                }
+                            }
+                            Err(err) => warn!("Failed to serialize blocks: {}", err),
+                        }
+                    }
+                    Key::Character(c) if c.eq_ignore_ascii_case("v") => {
+                        let text = match clipboard::get_text() {
+                            Ok(text) => text,
+                            Err(err) => {
+                                warn!("Failed to read clipboard: {}", err);
+                                return;
+                            }
+                        };
+                        if let Some(snippet) = StackSnippet::from_json(&text) {
+                            let mut state = app_state.write();
+                            let missing = state.paste_blocks(snippet.blocks, cursor());
+                            if !missing.is_empty() {
+                                let names = missing
+                                    .iter()
+                                    .map(|d| format!("{} (v{})", d.id, d.version))
+                                    .collect::<Vec<_>>()
+                                    .join(", ");
+                                state.notify(format!("不足している定義: {}", names));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            },
 
             onmousemove: {
                 move |e: MouseEvent| {
+                    cursor.set((e.page_coordinates().x, e.page_coordinates().y));
                     let dragging_info = app_state.read().dragging.clone();
                     if let Some((id, offset_x, offset_y)) = dragging_info {
                         let mouse_x = e.page_coordinates().x;
@@ -37,13 +92,38 @@ pub fn App() -> Element {
                             blockY = mouse_y - offset_y;
                         }
 
-                        app_state.write().update_block_position(&id, blockX, blockY);
+                        app_state.write().move_stack(&id, blockX, blockY);
                     }
                 }
             },
             onmouseup: {
                 move |_| {
-                    app_state.write().dragging = None;
+                    let mut state = app_state.write();
+                    let Some((id, clicked)) = state.end_drag() else {
+                        return;
+                    };
+                    state.snap_block(&id);
+                    let Some(expression) = state.reporter_expression(&id).filter(|_| clicked) else {
+                        return;
+                    };
+                    let input = state.preview_input();
+                    state.bubble = Some(ValueBubble { block_id: id.clone(), result: None });
+                    drop(state);
+
+                    spawn(async move {
+                        let result = match expression {
+                            Ok(code) => sandbox::evaluate(code, input)
+                                .await
+                                .map(|v| preview::format_value(&v))
+                                .map_err(|e| e.to_string()),
+                            Err(message) => Err(message),
+                        };
+                        let mut state = app_state.write();
+                        // 待っている間に別のブロックを触っていたら捨てる
+                        if state.bubble.as_ref().is_some_and(|b| b.block_id == id) {
+                            state.bubble = Some(ValueBubble { block_id: id, result: Some(result) });
+                        }
+                    });
                 }
             },
 
@@ -52,9 +132,19 @@ pub fn App() -> Element {
             div {
                 id: "canvas-output",
 
-                crate::components::board::Board {},
+                div {
+                    id: "board-split",
+                    class: if app_state.read().settings.show_code_editor { "split" } else { "" },
+
+                    crate::components::board::Board {},
+                    if app_state.read().settings.show_code_editor {
+                        crate::components::code_editor::CodeEditor {}
+                    }
+                }
                 crate::components::output::Output {}
             }
+
+            crate::components::toast::Toast {}
         }
     }
 }
@@ -0,0 +1,73 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::models::block::Block;
+use crate::state::app_state::AppState;
+use crate::state::project::Project;
+use crate::utils::export;
+use crate::utils::share::{self, SharePayload};
+
+const USAGE: &str = "usage: tpl-dioxus export <project.json | share-code.txt> <output.svg | output.png> [--stack <block-id>] [--scale <n>]";
+
+// 大きすぎる倍率は画像の確保で落ちるので範囲に収める
+const SCALE_RANGE: (f64, f64) = (0.1, 16.0);
+
+fn load_blocks(input: &str) -> Result<Vec<Block>, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(input)?;
+    if text.trim_start().starts_with(share::SHARE_PREFIX) {
+        return Ok(match share::decode(&text)? {
+            SharePayload::Stack(blocks) => blocks,
+            SharePayload::Project(project) => project.blocks,
+        });
+    }
+    let project: Project = serde_json::from_str(&text)?;
+    Ok(project.blocks)
+}
+
+// ウィンドウを開かずに画像を書き出す
+pub fn export(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut positional = Vec::new();
+    let mut stack = None;
+    let mut scale = 2.0;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--stack" => stack = iter.next().cloned(),
+            "--scale" => {
+                let value = iter.next().and_then(|s| s.parse::<f64>().ok()).filter(|s| s.is_finite()).ok_or(USAGE)?;
+                scale = value.clamp(SCALE_RANGE.0, SCALE_RANGE.1);
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+    let [input, output] = positional.as_slice() else {
+        return Err(USAGE.into());
+    };
+
+    let mut blocks = load_blocks(input)?;
+    if let Some(root) = stack {
+        let state = AppState {
+            blocks: blocks.into_iter().map(|b| (b.id.clone(), b)).collect(),
+            ..Default::default()
+        };
+        blocks = state.copy_stack(&root);
+        if blocks.is_empty() {
+            return Err(format!("block not found: {}", root).into());
+        }
+    }
+
+    export::save(&blocks, &PathBuf::from(output), scale)?;
+    Ok(())
+}
+
+// リリース版の Windows はコンソールを持たないので、起動元のコンソールに出力をつなぐ
+#[cfg(all(windows, not(debug_assertions)))]
+pub fn attach_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(all(windows, not(debug_assertions))))]
+pub fn attach_console() {}
@@ -1,13 +1,17 @@
-use crate::models::block::BlockContent;
-use crate::state::app_state::AppState;
-use crate::utils::block::{generate_path_string, PathType};
+use crate::models::block::{BlockContent, ValueType};
+use crate::components::slot::Slot;
+use crate::state::app_state::{AppState, DefinitionStatus};
+use crate::utils::block::{
+    block_colors, generate_loop_path_string, generate_path_string, PathType, DEFAULT_MOUTH_HEIGHT, LOOP_ARM_HEIGHT,
+};
 use crate::{
     components::block,
-    models::block::{Block, EnumBlockContent},
+    models::block::{Block, BlockType, EnumBlockContent},
     state::app_state::BlockList,
     utils::block::Size,
 };
 use tracing::{info, warn, error, debug};
+use dioxus::html::input_data::MouseButton;
 use dioxus::prelude::*;
 
 #[derive(Clone, PartialEq)]
@@ -29,7 +33,7 @@ pub fn DraggableBlock(
 
     let (block_id, _is_list) = match &block_data {
         BlockData::Block(block) => (block.id.clone(), false),
-        BlockData::BlockList(block_list) => (block_list.name.clone(), true),
+        BlockData::BlockList(block_list) => (block_list.id.clone(), true),
     };
 
     let block_contents = match &block_data {
@@ -37,8 +41,57 @@ pub fn DraggableBlock(
         BlockData::BlockList(block_list) => block_list.block.content.clone(),
     };
 
+    let block_type = match &block_data {
+        BlockData::Block(block) => block.block_type.clone(),
+        BlockData::BlockList(block_list) => block_list.block.block_type.clone(),
+    };
+    let block_title = match &block_data {
+        BlockData::Block(block) => block.title.clone(),
+        BlockData::BlockList(block_list) => block_list.block.title.clone(),
+    };
+    let output_type = match &block_data {
+        BlockData::Block(block) => block.output_type.clone(),
+        BlockData::BlockList(block_list) => block_list.block.output_type.clone(),
+    };
+    let definition_status = match &block_data {
+        BlockData::Block(block) => app_state.read().definition_status(block),
+        BlockData::BlockList(_) => DefinitionStatus::Resolved,
+    };
+    let is_selected = app_state.read().selected.as_ref() == Some(&block_id);
+    let is_highlighted = app_state.read().highlighted_block.as_ref() == Some(&block_id);
+    let is_debugging = app_state.read().debug_block.as_ref() == Some(&block_id);
+    let is_placed = matches!(block_data, BlockData::Block(_));
+    // どの Flag の出力にも入らないブロックは薄く描く
+    let is_unreachable = is_placed && !app_state.read().is_reachable(&block_id);
+    let in_hovered_stack = is_placed && app_state.read().hovered_stack.contains(&block_id);
+    let has_breakpoint = matches!(block_data, BlockData::Block(_)) && app_state.read().breakpoints.contains(&block_id);
+    let evaluation_error = match &block_data {
+        BlockData::Block(_) => app_state.read().evaluation_errors.get(&block_id).cloned(),
+        BlockData::BlockList(_) => None,
+    };
+    let bubble = match &block_data {
+        BlockData::Block(_) => app_state.read().bubble.clone().filter(|b| b.block_id == block_id),
+        BlockData::BlockList(_) => None,
+    };
+    let (fill, stroke) = match definition_status {
+        DefinitionStatus::Resolved => block_colors(&block_type),
+        _ => ("#A0A0A0", "#7A7A7A"),
+    };
+    let unknown_label = match (&definition_status, &block_data) {
+        (DefinitionStatus::Missing, BlockData::Block(block)) => Some(format!("unknown: {}", block.title)),
+        (DefinitionStatus::Newer { library_version }, BlockData::Block(block)) => Some(format!(
+            "unknown: {} (v{} > v{})",
+            block.title,
+            block.definition.as_ref().map(|d| d.version).unwrap_or_default(),
+            library_version
+        )),
+        _ => None,
+    };
+
     let mut is_calculating = use_signal(|| false);
 
+    let size_block_id = block_id.clone();
+
     // サイズ計算関数を簡素化
     let calculate_size = use_callback(move |_| {
         if is_calculating() {
@@ -50,6 +103,7 @@ pub fn DraggableBlock(
         
         spawn({
             let content_ref = content_ref.clone();
+            let size_block_id = size_block_id.clone();
             
             async move {
                 // 短い遅延でDOMの安定を待つ
@@ -73,7 +127,10 @@ pub fn DraggableBlock(
                                 || (current_size.height - new_height).abs() > 1.0;
                             
                             if size_changed {
-                                size_signal.set(new_size);
+                                size_signal.set(new_size.clone());
+                                if !_is_list {
+                                    app_state.write().set_block_size(&size_block_id, new_size);
+                                }
                             }
                         }
                     }
@@ -104,14 +161,23 @@ pub fn DraggableBlock(
     });
 
     let mut size = size_signal();
-    let path = generate_path_string(&PathType::Move, &size);
+    let (path, svg_height) = if block_type == BlockType::Control {
+        let mouth = match &block_data {
+            BlockData::Block(_) => app_state.read().mouth_height(&block_id),
+            BlockData::BlockList(_) => DEFAULT_MOUTH_HEIGHT,
+        };
+        // 下の腕と突起の分だけ SVG を伸ばす
+        (generate_loop_path_string(&size, mouth), size.height + mouth + LOOP_ARM_HEIGHT)
+    } else {
+        (generate_path_string(&PathType::from(&block_type), &size), size.height)
+    };
 
     rsx! {
         div {
             class: "_block",
             id: block_id.clone(),
             style: {format!(
-                "width: {}px; height: {}px; position: {}; left: {}px; top: {}px; z-index: {}; cursor: {}; margin: {}; filter: drop-shadow({});",
+                "width: {}px; height: {}px; position: {}; left: {}px; top: {}px; z-index: {}; cursor: {}; margin: {}; filter: drop-shadow({}); pointer-events: {}; opacity: {};",
                 size.width,
                 size.height,
                 if let BlockData::Block(_) = &block_data { if is_dragging() { "fixed" } else { "absolute" } } else { "relative" },
@@ -120,12 +186,46 @@ pub fn DraggableBlock(
                 if is_dragging() { "1000" } else { "1" },
                 if is_dragging() { "grabbing" } else { "grab" },
                 if is_dragging() { "0 0 1px 0" } else { "1px 0 0 0" },
-                if is_dragging() { "0 4px 6px rgba(90, 141, 238, 0.5)" } else { "none" }
+                if is_dragging() { "0 4px 6px rgba(90, 141, 238, 0.5)" } else if is_debugging { "0 0 8px #FFD500" } else if is_highlighted { "0 0 6px #FF4D8D" } else if is_selected { "0 0 4px #FFB300" } else if in_hovered_stack { "0 0 6px #FFBF00" } else { "none" },
+                // ドロップ先のスロットにポインタのイベントを通す
+                if is_dragging() { "none" } else { "auto" },
+                if is_unreachable && !is_dragging() { "0.45" } else { "1" }
             )},
+            onmouseenter: {
+                let block_id = block_id.clone();
+                let is_flag = is_placed && block_type == BlockType::Flag;
+                move |_| {
+                    if is_flag && app_state.read().dragging.is_none() {
+                        app_state.write().hover_flag(Some(&block_id));
+                    }
+                }
+            },
+            onmouseleave: {
+                let is_flag = is_placed && block_type == BlockType::Flag;
+                move |_| {
+                    if is_flag {
+                        app_state.write().hover_flag(None);
+                    }
+                }
+            },
+            oncontextmenu: {
+                let block_id = block_id.clone();
+                move |e: MouseEvent| {
+                    e.stop_propagation();
+                    if is_placed {
+                        e.prevent_default();
+                        let point = e.client_coordinates();
+                        app_state.write().context_menu = Some((block_id.clone(), point.x, point.y));
+                    }
+                }
+            },
             onmousedown: {
                 let block_id = block_id.clone();
                 move |e: MouseEvent| {
                     e.stop_propagation();
+                    if e.trigger_button() == Some(MouseButton::Secondary) {
+                        return;
+                    }
 
                     match &block_data {
                         BlockData::Block(_) => {
@@ -135,12 +235,19 @@ pub fn DraggableBlock(
                         BlockData::BlockList(block_list) => {
                             let mut state = app_state.write();
                             let rect = e.element_coordinates();
-                            let id = state.add_block(&block_list.name, rect.x - 250.0, rect.y);
+                            let id = state.add_block(&block_list.id, rect.x - 250.0, rect.y);
 
-                            state.dragging = match id {
-                                Some(id) => Some((id, 0.0, 0.0)),
-                                None => None
+                            let Some(id) = id else {
+                                return;
                             };
+                            state.start_drag(&id, 0.0, 0.0);
+                            // パレットから置いたときだけ最近使ったブロックに載せる
+                            state.palette.touch(&block_list.id);
+                            let palette = state.palette.clone();
+                            drop(state);
+                            if let Err(e) = palette.save() {
+                                warn!("Failed to save palette history: {}", e);
+                            }
                         }
                     }
                 }
@@ -149,44 +256,75 @@ pub fn DraggableBlock(
             div {
                 style: "position: relative; width: {size.width}px; height: {size.height}px;",
 
+                if let Some(bubble) = bubble {
+                    match bubble.result {
+                        Some(Ok(text)) => rsx! { div { class: "value-bubble", "{text}" } },
+                        Some(Err(message)) => rsx! { div { class: "value-bubble error", "{message}" } },
+                        None => rsx! { div { class: "value-bubble pending", "…" } },
+                    }
+                }
+
+                if has_breakpoint {
+                    div {
+                        class: "block-breakpoint",
+                        title: "ブレークポイントを解除",
+                        onmousedown: {
+                            let block_id = block_id.clone();
+                            move |e: MouseEvent| {
+                                e.stop_propagation();
+                                app_state.write().toggle_breakpoint(&block_id);
+                            }
+                        },
+                    }
+                }
+
                 div {
                     class: "block-content",
                     style: "",
 
+                    if let Some(label) = &unknown_label {
+                        p { class: "block-unknown", "{label}" }
+                    }
+
+                    if let (BlockType::Flag, BlockData::Block(block)) = (&block_type, &block_data) {
+                        button {
+                            class: "flag-button",
+                            title: "実行",
+                            onmousedown: move |e: MouseEvent| e.stop_propagation(),
+                            onclick: {
+                                let flag_id = block.id.clone();
+                                move |e: MouseEvent| {
+                                    e.stop_propagation();
+                                    app_state.write().run_flag(&flag_id);
+                                }
+                            },
+                            "⚑"
+                        }
+                    }
+
+                    if let BlockData::Block(Block { target: Some(target), .. }) = &block_data {
+                        span { class: "flag-target", "→ {target.label()}" }
+                    }
+
+                    if let Some(message) = &evaluation_error {
+                        span { class: "flag-warning", title: "{message}", "⚠" }
+                    }
+
+                    // フィールドのないブロックは名前を表示する
+                    if block_type != BlockType::Flag && block_contents.is_empty() {
+                        p { class: "block-title", "{block_title}" }
+                    }
+
                     for block_content in block_contents.iter() {
                         match &block_content.content {
-                            EnumBlockContent::ContentValue(content_value) => {
+                            EnumBlockContent::ContentValue(_) => {
                                 rsx! {
-                                    div {
-                                        class: "content-value",
-
-                                        label {
-                                            "{content_value.title.to_string()}"
-                                        },
-
-                                        input {
-                                            onmousedown: move |e: MouseEvent| {
-                                                e.stop_propagation();
-                                            },
-                                            oninput: {
-                                                let content_id = block_content.id.clone();
-                                                let block_id = block_id.clone();
-                                                let calculate_size = calculate_size.clone();
-                                                move |e: Event<FormData>| {
-                                                    e.stop_propagation();
-                                                    let mut state = app_state.write();
-                                                    let value = e.value();
-
-                                                    state.update_block_content(&block_id, &content_id, &value);
-                                                    drop(state);
-
-                                                    calculate_size(());
-                                                }
-                                            },
-                                            style: "border: 2px solid #3A6BC1; border-radius: 9999px; min-width: 60px; width: auto; max-width: calc(100% - 40px); flex: 0 1 auto;",
-                                            type: "text",
-                                            value: "{content_value.value}",
-                                        },
+                                    Slot {
+                                        host_id: block_id.clone(),
+                                        content: block_content.clone(),
+                                        stroke: stroke.to_string(),
+                                        placed: !_is_list,
+                                        on_change: move |_| calculate_size(()),
                                     }
                                 }
                             },
@@ -198,17 +336,21 @@ pub fn DraggableBlock(
                             }
                         }
                     }
+
+                    if output_type != ValueType::Any {
+                        span { class: "type-badge output", title: "{output_type.label()}", "→ {output_type.badge()}" }
+                    }
                 }
 
                 svg {
                     width: "{size.width}px",
-                    height: "{size.height}px",
+                    height: "{svg_height}px",
 
                     path {
-                        fill: "#5A8DEE",
+                        fill: fill,
                         stroke_width: "2.5",
-                        stroke: "#3A6BC1",
-                        style: "filter: drop-shadow(0 4px 0 #3A6BC1);",
+                        stroke: stroke,
+                        style: "filter: drop-shadow(0 4px 0 {stroke});",
                         d: "{path}",
                     }
                 }
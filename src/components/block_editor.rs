@@ -0,0 +1,456 @@
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::models::block::{
+    Block, BlockContent, BlockType, Connection, ContentSelector, ContentValue, EnumBlockContent,
+    Options, Position, Separator, ValueType,
+};
+use crate::state::app_state::{AppState, BlockList};
+use crate::utils::block::{block_colors, estimate_size, generate_path_string, PathType};
+use crate::utils::template;
+
+// 削除したフィールドの ID を使い回さないよう、既存の fieldN の最大値の次にする
+fn next_field_id(contents: &[BlockContent]) -> String {
+    let last = contents
+        .iter()
+        .filter_map(|c| c.id.strip_prefix("field").and_then(|n| n.parse::<usize>().ok()))
+        .max()
+        .unwrap_or(0);
+    format!("field{}", last + 1)
+}
+
+fn new_field(id: String, kind: &str) -> BlockContent {
+    let content = match kind {
+        "selector" => EnumBlockContent::ContentSelector(ContentSelector {
+            title: String::new(),
+            value: String::new(),
+            options: Vec::new(),
+            placeholder: None,
+        }),
+        "separator" => EnumBlockContent::Separator(Separator::Space),
+        _ => EnumBlockContent::ContentValue(ContentValue {
+            title: String::new(),
+            value: String::new(),
+            placeholder: None,
+            value_type: ValueType::Any,
+            reporter: None,
+        }),
+    };
+    BlockContent {
+        id,
+        content,
+    }
+}
+
+fn parse_options(text: &str) -> Vec<Options> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .enumerate()
+        .map(|(index, item)| {
+            let (title, value) = item.split_once('=').unwrap_or((item, item));
+            Options {
+                id: format!("option{}", index + 1),
+                title: title.trim().to_string(),
+                value: value.trim().to_string(),
+            }
+        })
+        .collect()
+}
+
+fn format_options(options: &[Options]) -> String {
+    options
+        .iter()
+        .map(|o| if o.title == o.value { o.value.clone() } else { format!("{}={}", o.title, o.value) })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn validate(title: &str, block_type: &BlockType, contents: &[BlockContent], output: &str) -> Result<(), String> {
+    if title.trim().is_empty() {
+        return Err("タイトルを入力してください".to_string());
+    }
+    for (index, content) in contents.iter().enumerate() {
+        if content.id.trim().is_empty() {
+            return Err(format!("{} 番目のフィールドに ID がありません", index + 1));
+        }
+        if contents[..index].iter().any(|c| c.id == content.id) {
+            return Err(format!("フィールド ID \"{}\" が重複しています", content.id));
+        }
+    }
+    for name in template::placeholders(output) {
+        if name == template::BODY_PLACEHOLDER && *block_type == BlockType::Control {
+            continue;
+        }
+        let known = contents
+            .iter()
+            .any(|c| c.id == name && !matches!(c.content, EnumBlockContent::Separator(_)));
+        if !known {
+            return Err(format!("未定義のフィールド {{{{{}}}}} が出力に含まれています", name));
+        }
+    }
+    Ok(())
+}
+
+#[component]
+pub fn BlockEditor(editing: Option<BlockList>, on_close: EventHandler<()>) -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    let is_editing = editing.is_some();
+    let initial = editing.as_ref().map(|list| list.block.clone());
+    let mut title = use_signal(|| initial.as_ref().map(|b| b.title.clone()).unwrap_or_default());
+    let mut block_type = use_signal(|| initial.as_ref().map(|b| b.block_type.clone()).unwrap_or(BlockType::Move));
+    let mut connection = use_signal(|| initial.as_ref().map(|b| b.connection.clone()).unwrap_or(Connection::Both));
+    let mut contents = use_signal(|| initial.as_ref().map(|b| b.content.clone()).unwrap_or_default());
+    let mut output = use_signal(|| initial.as_ref().map(|b| b.output.clone()).unwrap_or_default());
+    let mut output_type = use_signal(|| initial.as_ref().map(|b| b.output_type.clone()).unwrap_or_default());
+    let mut category = use_signal(|| editing.as_ref().and_then(|list| list.category.clone()).unwrap_or_default());
+    let mut tags = use_signal(|| editing.as_ref().map(|list| list.tags.join(", ")).unwrap_or_default());
+    let mut error = use_signal(|| None::<String>);
+
+    let preview = Block {
+        content: contents(),
+        output: output(),
+        connection: connection(),
+        output_type: output_type(),
+        ..Block::new(String::new(), block_type(), title(), Position { x: 0.0, y: 0.0 })
+    };
+    let size = estimate_size(&preview.content);
+    let path = generate_path_string(&PathType::from(&preview.block_type), &size);
+    let (fill, stroke) = block_colors(&preview.block_type);
+    let rendered_output = template::render(&preview.output, |name| preview.content_value(name));
+
+    let on_save = move |_| {
+        let contents = contents();
+        if let Err(message) = validate(&title(), &block_type(), &contents, &output()) {
+            error.set(Some(message));
+            return;
+        }
+        let block = Block {
+            content: contents,
+            output: output(),
+            connection: connection(),
+            output_type: output_type(),
+            ..Block::new(String::new(), block_type(), title().trim().to_string(), Position { x: 0.0, y: 0.0 })
+        };
+        let mut state = app_state.write();
+        let mut block_list = match &editing {
+            Some(existing) => BlockList {
+                version: existing.version + 1,
+                block,
+                ..existing.clone()
+            },
+            None => {
+                let name = format!("custom_{}", Uuid::new_v4().simple());
+                BlockList::new(&state.user_pack.id.clone(), &name, 1, block)
+            }
+        };
+        let category = category().trim().to_string();
+        block_list.category = if category.is_empty() { None } else { Some(category) };
+        block_list.tags = tags()
+            .split(',')
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        match state.save_custom_block(block_list) {
+            Ok(()) => on_close.call(()),
+            Err(e) => error.set(Some(format!("保存に失敗しました: {}", e))),
+        }
+    };
+
+    rsx! {
+        div {
+            class: "dialog-backdrop",
+            onmousedown: move |e: MouseEvent| e.stop_propagation(),
+            onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+
+            div {
+                class: "dialog",
+
+                h2 {
+                    if is_editing { "カスタムブロックの編集" } else { "カスタムブロックの作成" }
+                }
+
+                div {
+                    class: "dialog-row",
+                    label { "タイトル" }
+                    input {
+                        r#type: "text",
+                        value: "{title}",
+                        oninput: move |e: Event<FormData>| title.set(e.value()),
+                    }
+                }
+
+                div {
+                    class: "dialog-row",
+                    label { "種類" }
+                    select {
+                        onchange: move |e: Event<FormData>| {
+                            if let Some(t) = BlockType::all().into_iter().find(|t| format!("{:?}", t) == e.value()) {
+                                block_type.set(t);
+                            }
+                        },
+                        for t in BlockType::all() {
+                            option {
+                                value: "{t:?}",
+                                selected: t == block_type(),
+                                "{t:?}"
+                            }
+                        }
+                    }
+                    label { "接続" }
+                    select {
+                        onchange: move |e: Event<FormData>| {
+                            if let Some(c) = Connection::all().into_iter().find(|c| format!("{:?}", c) == e.value()) {
+                                connection.set(c);
+                            }
+                        },
+                        for c in Connection::all() {
+                            option {
+                                value: "{c:?}",
+                                selected: c == connection(),
+                                "{c:?}"
+                            }
+                        }
+                    }
+                    label { "出力の型" }
+                    select {
+                        onchange: move |e: Event<FormData>| {
+                            if let Some(t) = ValueType::all().into_iter().find(|t| format!("{:?}", t) == e.value()) {
+                                output_type.set(t);
+                            }
+                        },
+                        for t in ValueType::all() {
+                            option {
+                                value: "{t:?}",
+                                selected: t == output_type(),
+                                "{t.label()}"
+                            }
+                        }
+                    }
+                }
+
+                div {
+                    class: "dialog-row",
+                    label { "カテゴリ" }
+                    input {
+                        r#type: "text",
+                        value: "{category}",
+                        oninput: move |e: Event<FormData>| category.set(e.value()),
+                    }
+                    label { "タグ" }
+                    input {
+                        r#type: "text",
+                        placeholder: "カンマ区切り",
+                        value: "{tags}",
+                        oninput: move |e: Event<FormData>| tags.set(e.value()),
+                    }
+                }
+
+                h3 { "フィールド" }
+
+                for (index, field) in contents().into_iter().enumerate() {
+                    div {
+                        key: "{index}",
+                        class: "dialog-field",
+
+                        input {
+                            class: "field-id",
+                            r#type: "text",
+                            title: "ID",
+                            value: "{field.id}",
+                            oninput: move |e: Event<FormData>| {
+                                if let Some(c) = contents.write().get_mut(index) {
+                                    c.id = e.value().trim().to_string();
+                                }
+                            },
+                        }
+
+                        match field.content {
+                            EnumBlockContent::ContentValue(cv) => rsx! {
+                                input {
+                                    r#type: "text",
+                                    placeholder: "ラベル",
+                                    value: "{cv.title}",
+                                    oninput: move |e: Event<FormData>| {
+                                        if let Some(BlockContent { content: EnumBlockContent::ContentValue(cv), .. }) = contents.write().get_mut(index) {
+                                            cv.title = e.value();
+                                        }
+                                    },
+                                }
+                                input {
+                                    r#type: "text",
+                                    placeholder: "初期値",
+                                    value: "{cv.value}",
+                                    oninput: move |e: Event<FormData>| {
+                                        if let Some(BlockContent { content: EnumBlockContent::ContentValue(cv), .. }) = contents.write().get_mut(index) {
+                                            cv.value = e.value();
+                                        }
+                                    },
+                                }
+                                input {
+                                    r#type: "text",
+                                    placeholder: "プレースホルダ",
+                                    value: cv.placeholder.clone().unwrap_or_default(),
+                                    oninput: move |e: Event<FormData>| {
+                                        if let Some(BlockContent { content: EnumBlockContent::ContentValue(cv), .. }) = contents.write().get_mut(index) {
+                                            let value = e.value();
+                                            cv.placeholder = if value.is_empty() { None } else { Some(value) };
+                                        }
+                                    },
+                                }
+                                select {
+                                    title: "型",
+                                    onchange: move |e: Event<FormData>| {
+                                        if let Some(BlockContent { content: EnumBlockContent::ContentValue(cv), .. }) = contents.write().get_mut(index) {
+                                            if let Some(t) = ValueType::all().into_iter().find(|t| format!("{:?}", t) == e.value()) {
+                                                cv.value_type = t;
+                                            }
+                                        }
+                                    },
+                                    for t in ValueType::all() {
+                                        option {
+                                            value: "{t:?}",
+                                            selected: t == cv.value_type,
+                                            "{t.label()}"
+                                        }
+                                    }
+                                }
+                            },
+                            EnumBlockContent::ContentSelector(cs) => rsx! {
+                                input {
+                                    r#type: "text",
+                                    placeholder: "ラベル",
+                                    value: "{cs.title}",
+                                    oninput: move |e: Event<FormData>| {
+                                        if let Some(BlockContent { content: EnumBlockContent::ContentSelector(cs), .. }) = contents.write().get_mut(index) {
+                                            cs.title = e.value();
+                                        }
+                                    },
+                                }
+                                input {
+                                    r#type: "text",
+                                    placeholder: "選択肢 (表示名=値, ...)",
+                                    value: format_options(&cs.options),
+                                    onchange: move |e: Event<FormData>| {
+                                        if let Some(BlockContent { content: EnumBlockContent::ContentSelector(cs), .. }) = contents.write().get_mut(index) {
+                                            cs.options = parse_options(&e.value());
+                                            if !cs.options.iter().any(|o| o.value == cs.value) {
+                                                cs.value = cs.options.first().map(|o| o.value.clone()).unwrap_or_default();
+                                            }
+                                        }
+                                    },
+                                }
+                            },
+                            EnumBlockContent::Separator(separator) => rsx! {
+                                select {
+                                    onchange: move |e: Event<FormData>| {
+                                        if let Some(BlockContent { content: EnumBlockContent::Separator(s), .. }) = contents.write().get_mut(index) {
+                                            *s = match e.value().as_str() {
+                                                "Newline" => Separator::Newline,
+                                                "None" => Separator::None,
+                                                _ => Separator::Space,
+                                            };
+                                        }
+                                    },
+                                    for s in [Separator::Space, Separator::Newline, Separator::None] {
+                                        option {
+                                            value: "{s:?}",
+                                            selected: s == separator,
+                                            "{s:?}"
+                                        }
+                                    }
+                                }
+                            },
+                        }
+
+                        button {
+                            onclick: move |_| {
+                                contents.write().remove(index);
+                            },
+                            "削除"
+                        }
+                    }
+                }
+
+                div {
+                    class: "dialog-row",
+                    for (kind, label) in [("value", "+ 入力"), ("selector", "+ 選択"), ("separator", "+ 区切り")] {
+                        button {
+                            onclick: move |_| {
+                                let id = next_field_id(&contents.read());
+                                contents.write().push(new_field(id, kind));
+                            },
+                            "{label}"
+                        }
+                    }
+                }
+
+                h3 { "出力" }
+                textarea {
+                    class: "dialog-output",
+                    placeholder: "{{{{field1}}}} でフィールドの値を埋め込みます",
+                    value: "{output}",
+                    oninput: move |e: Event<FormData>| output.set(e.value()),
+                }
+
+                h3 { "プレビュー" }
+                div {
+                    class: "dialog-preview",
+
+                    div {
+                        style: "position: relative; width: {size.width}px; height: {size.height}px;",
+
+                        div {
+                            class: "block-content",
+
+                            for block_content in preview.content.iter() {
+                                match &block_content.content {
+                                    EnumBlockContent::ContentValue(cv) => rsx! {
+                                        div {
+                                            class: "content-value",
+                                            label { "{cv.title}" }
+                                            input {
+                                                style: "border: 2px solid {stroke}; border-radius: 9999px;",
+                                                r#type: "text",
+                                                disabled: true,
+                                                value: "{cv.value}",
+                                                placeholder: cv.placeholder.clone().unwrap_or_default(),
+                                            }
+                                        }
+                                    },
+                                    EnumBlockContent::ContentSelector(cs) => rsx! { p { "{cs.title}" } },
+                                    EnumBlockContent::Separator(_) => rsx! { "" },
+                                }
+                            }
+                        }
+
+                        svg {
+                            width: "{size.width}px",
+                            height: "{size.height}px",
+
+                            path {
+                                fill: fill,
+                                stroke_width: "2.5",
+                                stroke: stroke,
+                                style: "filter: drop-shadow(0 4px 0 {stroke});",
+                                d: "{path}",
+                            }
+                        }
+                    }
+
+                    pre { class: "dialog-output-preview", "{rendered_output}" }
+                }
+
+                if let Some(message) = error() {
+                    p { class: "dialog-error", "{message}" }
+                }
+
+                div {
+                    class: "dialog-actions",
+                    button { onclick: move |_| on_close.call(()), "キャンセル" }
+                    button { onclick: on_save, "保存" }
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 use crate::components::block::{DraggableBlock, BlockData};
+use crate::components::extract::{ContextMenu, ExtractDialog};
 use crate::models::block::Block;
 use crate::models::block::Position;
 use crate::state::app_state::{AppState, BlockList};
@@ -10,6 +11,7 @@ use dioxus::prelude::*;
 pub fn Board() -> Element {
     let mut app_state = use_context::<Signal<AppState>>();
     let mut dragging_id = use_signal(|| None);
+    let mut extracting = use_signal(|| None::<String>);
 
     use_effect(move || {
         let dragging = app_state.read().dragging.clone();
@@ -18,8 +20,10 @@ pub fn Board() -> Element {
         );
     });
 
+    // スロットにはめ込まれた値ブロックはホストの中に描く
     let blocks = &app_state.read().blocks.iter()
         .filter(|(id, _)| Some(*id).clone() != dragging_id().as_ref())
+        .filter(|(id, _)| !app_state.read().is_plugged(id))
         .map(|(id, block)| {
             (id.clone(), block.clone())
         })
@@ -31,8 +35,8 @@ pub fn Board() -> Element {
             Some(rsx! {
                 DraggableBlock {
                     block_data: BlockData::Block(block.clone()),
-                    on_drag_start: move |(id, offset_x, offset_y)| {
-                        app_state.write().dragging = Some((id, offset_x, offset_y));
+                    on_drag_start: move |(id, offset_x, offset_y): (String, f64, f64)| {
+                        app_state.write().start_drag(&id, offset_x, offset_y);
                     }
                 }
             })
@@ -48,17 +52,40 @@ pub fn Board() -> Element {
             id: "canvas-wrapper",
             div {
                 id: "canvas",
+                onmousedown: move |_| {
+                    let mut state = app_state.write();
+                    state.selected = None;
+                    state.bubble = None;
+                    state.context_menu = None;
+                },
 
                 for (_, block) in blocks {
                     DraggableBlock {
                         block_data: BlockData::Block(block.clone()),
-                        on_drag_start: move |(id, offset_x, offset_y)| {
-                            app_state.write().dragging = Some((id, offset_x, offset_y));
+                        on_drag_start: move |(id, offset_x, offset_y): (String, f64, f64)| {
+                            app_state.write().start_drag(&id, offset_x, offset_y);
                         }
                     }
                 }
             }
 
+            if let Some((block_id, x, y)) = app_state.read().context_menu.clone() {
+                ContextMenu {
+                    block_id,
+                    x,
+                    y,
+                    on_extract: move |id: String| extracting.set(Some(id)),
+                }
+            }
+
+            if let Some(block_id) = extracting() {
+                ExtractDialog {
+                    key: "{block_id}",
+                    block_id,
+                    on_close: move |_| extracting.set(None),
+                }
+            }
+
             {
                 if let Some(dragging_block) = dragging_block {
                     dragging_block
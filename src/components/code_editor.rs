@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use dioxus::prelude::*;
+
+use crate::state::app_state::AppState;
+use crate::utils::generator;
+
+// 入力が止まってから解析する
+const PARSE_DELAY: Duration = Duration::from_millis(300);
+
+#[component]
+pub fn CodeEditor() -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    let mut text = use_signal(String::new);
+    let mut editing = use_signal(|| false);
+    let mut revision = use_signal(|| 0u64);
+
+    let state = app_state.read();
+    let flag = state.active_flag();
+    let generated = flag.as_ref().and_then(|id| generator::generate(&state, id));
+    let flag_title = generated.as_ref().map(|g| g.flag_title.clone());
+    // 変数宣言とマイブロックの定義は Flag と定義ブロックのものなので、読むだけにする
+    let preamble = generated.as_ref().map(|g| g.preamble().to_string()).unwrap_or_default();
+    let code = generated.as_ref().map(|g| g.stack_code().to_string()).unwrap_or_default();
+    drop(state);
+
+    // 編集中は入力したテキストをそのまま表示し、それ以外はブロックから生成する
+    let value = if editing() { text() } else { code.clone() };
+
+    rsx! {
+        div {
+            id: "code-editor",
+
+            div {
+                class: "code-editor-header",
+                match &flag_title {
+                    Some(title) => rsx! { "⚑ {title}" },
+                    None => rsx! { "Flag ブロックを置くとここで編集できます" },
+                }
+            }
+
+            if !preamble.is_empty() {
+                pre { class: "code-editor-preamble", title: "変数とマイブロックのブロックから生成されます", "{preamble}" }
+            }
+
+            textarea {
+                spellcheck: false,
+                disabled: flag.is_none(),
+                value: "{value}",
+                onmousedown: move |e: MouseEvent| e.stop_propagation(),
+                onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+                onfocus: {
+                    let code = code.clone();
+                    move |_| {
+                        text.set(code.clone());
+                        editing.set(true);
+                    }
+                },
+                onblur: {
+                    let flag = flag.clone();
+                    move |_| {
+                        // 解析待ちの入力があれば離れる前に反映する
+                        if let (true, Some(flag_id)) = (editing(), flag.clone()) {
+                            revision += 1;
+                            if text() != code {
+                                app_state.write().replace_stack_source(&flag_id, &text());
+                            }
+                        }
+                        editing.set(false);
+                    }
+                },
+                oninput: move |e: Event<FormData>| {
+                    let Some(flag_id) = flag.clone() else {
+                        return;
+                    };
+                    text.set(e.value());
+                    let current = revision() + 1;
+                    revision.set(current);
+                    spawn(async move {
+                        tokio::time::sleep(PARSE_DELAY).await;
+                        if revision() == current {
+                            app_state.write().replace_stack_source(&flag_id, &text());
+                        }
+                    });
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,402 @@
+use dioxus::prelude::*;
+
+use crate::models::comp::{Keyframe, MockComp, MockEffect, MockLayer, MockProperty, PropertyValue};
+use crate::state::app_state::AppState;
+
+// 入力途中の値を失わないよう、編集中は文字列のまま持つ
+#[derive(Clone, PartialEq)]
+struct PropertyDraft {
+    name: String,
+    value: String,
+    keyframes: String,
+}
+
+#[derive(Clone, PartialEq)]
+struct EffectDraft {
+    name: String,
+    params: Vec<PropertyDraft>,
+}
+
+#[derive(Clone, PartialEq)]
+struct LayerDraft {
+    name: String,
+    properties: Vec<PropertyDraft>,
+    effects: Vec<EffectDraft>,
+}
+
+#[derive(Clone, PartialEq)]
+struct CompDraft {
+    name: String,
+    frame_rate: String,
+    duration: String,
+    width: String,
+    height: String,
+    layers: Vec<LayerDraft>,
+}
+
+// "0=[0, 0]; 2=[100, 200]" の形式
+fn format_keyframes(keyframes: &[Keyframe]) -> String {
+    keyframes
+        .iter()
+        .map(|k| format!("{}={}", k.time, k.value.to_text()))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn parse_keyframes(text: &str) -> Option<Vec<Keyframe>> {
+    text.split(';')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let (time, value) = item.split_once('=')?;
+            Some(Keyframe {
+                time: time.trim().parse().ok()?,
+                value: PropertyValue::parse(value)?,
+            })
+        })
+        .collect()
+}
+
+fn parse_number(text: &str, label: &str) -> Result<f64, String> {
+    text.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|v| *v > 0.0)
+        .ok_or_else(|| format!("{}には正の数値を入力してください", label))
+}
+
+impl PropertyDraft {
+    fn new() -> Self {
+        Self {
+            name: String::new(),
+            value: "0".to_string(),
+            keyframes: String::new(),
+        }
+    }
+
+    fn from_property(property: &MockProperty) -> Self {
+        Self {
+            name: property.name.clone(),
+            value: property.value.to_text(),
+            keyframes: format_keyframes(&property.keyframes),
+        }
+    }
+
+    fn to_property(&self, owner: &str) -> Result<MockProperty, String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err(format!("{} に名前のないプロパティがあります", owner));
+        }
+        let value = PropertyValue::parse(&self.value)
+            .ok_or_else(|| format!("{} の \"{}\" の値は 0 や [960, 540] の形式で入力してください", owner, name))?;
+        let keyframes = parse_keyframes(&self.keyframes)
+            .ok_or_else(|| format!("{} の \"{}\" のキーフレームは 0=値; 1=値 の形式で入力してください", owner, name))?;
+        Ok(MockProperty {
+            name: name.to_string(),
+            value,
+            keyframes,
+        })
+    }
+}
+
+impl CompDraft {
+    fn from_comp(comp: &MockComp) -> Self {
+        Self {
+            name: comp.name.clone(),
+            frame_rate: comp.frame_rate.to_string(),
+            duration: comp.duration.to_string(),
+            width: comp.width.to_string(),
+            height: comp.height.to_string(),
+            layers: comp
+                .layers
+                .iter()
+                .map(|layer| LayerDraft {
+                    name: layer.name.clone(),
+                    properties: layer.properties.iter().map(PropertyDraft::from_property).collect(),
+                    effects: layer
+                        .effects
+                        .iter()
+                        .map(|effect| EffectDraft {
+                            name: effect.name.clone(),
+                            params: effect.params.iter().map(PropertyDraft::from_property).collect(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    fn to_comp(&self) -> Result<MockComp, String> {
+        let mut layers: Vec<MockLayer> = Vec::new();
+        for layer in &self.layers {
+            let name = layer.name.trim();
+            if name.is_empty() {
+                return Err("名前のないレイヤーがあります".to_string());
+            }
+            if layers.iter().any(|l| l.name == name) {
+                return Err(format!("レイヤー名 \"{}\" が重複しています", name));
+            }
+            let owner = format!("レイヤー \"{}\"", name);
+            let properties = layer
+                .properties
+                .iter()
+                .map(|p| p.to_property(&owner))
+                .collect::<Result<Vec<_>, _>>()?;
+            let effects = layer
+                .effects
+                .iter()
+                .map(|effect| {
+                    let owner = format!("エフェクト \"{}\"", effect.name.trim());
+                    Ok(MockEffect {
+                        name: effect.name.trim().to_string(),
+                        params: effect.params.iter().map(|p| p.to_property(&owner)).collect::<Result<_, _>>()?,
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            layers.push(MockLayer {
+                name: name.to_string(),
+                properties,
+                effects,
+            });
+        }
+        Ok(MockComp {
+            name: self.name.trim().to_string(),
+            frame_rate: parse_number(&self.frame_rate, "フレームレート")?,
+            duration: parse_number(&self.duration, "デュレーション")?,
+            width: parse_number(&self.width, "幅")?,
+            height: parse_number(&self.height, "高さ")?,
+            layers,
+        })
+    }
+}
+
+#[component]
+fn PropertyRow(property: PropertyDraft, on_change: EventHandler<PropertyDraft>, on_remove: EventHandler<()>) -> Element {
+    let name_property = property.clone();
+    let value_property = property.clone();
+    let keyframes_property = property.clone();
+
+    rsx! {
+        div {
+            class: "dialog-field",
+            input {
+                r#type: "text",
+                placeholder: "名前",
+                value: "{property.name}",
+                oninput: move |e: Event<FormData>| on_change.call(PropertyDraft { name: e.value(), ..name_property.clone() }),
+            }
+            input {
+                r#type: "text",
+                placeholder: "値",
+                value: "{property.value}",
+                oninput: move |e: Event<FormData>| on_change.call(PropertyDraft { value: e.value(), ..value_property.clone() }),
+            }
+            input {
+                r#type: "text",
+                placeholder: "キーフレーム (秒=値; ...)",
+                value: "{property.keyframes}",
+                oninput: move |e: Event<FormData>| on_change.call(PropertyDraft { keyframes: e.value(), ..keyframes_property.clone() }),
+            }
+            button { onclick: move |_| on_remove.call(()), "削除" }
+        }
+    }
+}
+
+#[component]
+pub fn CompDialog(on_close: EventHandler<()>) -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    let mut draft = use_signal(|| CompDraft::from_comp(&app_state.read().comp));
+    let mut current = use_signal(|| 0usize);
+    let mut error = use_signal(|| None::<String>);
+
+    let comp = draft();
+    let layer_index = current().min(comp.layers.len().saturating_sub(1));
+    let layer = comp.layers.get(layer_index).cloned();
+
+    rsx! {
+        div {
+            class: "dialog-backdrop",
+            onmousedown: move |e: MouseEvent| e.stop_propagation(),
+            onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+
+            div {
+                class: "dialog",
+
+                h2 { "コンポジション" }
+
+                div {
+                    class: "dialog-row",
+                    label { "名前" }
+                    input {
+                        r#type: "text",
+                        value: "{comp.name}",
+                        oninput: move |e: Event<FormData>| draft.write().name = e.value(),
+                    }
+                    label { "fps" }
+                    input {
+                        r#type: "text",
+                        value: "{comp.frame_rate}",
+                        oninput: move |e: Event<FormData>| draft.write().frame_rate = e.value(),
+                    }
+                    label { "秒" }
+                    input {
+                        r#type: "text",
+                        value: "{comp.duration}",
+                        oninput: move |e: Event<FormData>| draft.write().duration = e.value(),
+                    }
+                }
+                div {
+                    class: "dialog-row",
+                    label { "幅" }
+                    input {
+                        r#type: "text",
+                        value: "{comp.width}",
+                        oninput: move |e: Event<FormData>| draft.write().width = e.value(),
+                    }
+                    label { "高さ" }
+                    input {
+                        r#type: "text",
+                        value: "{comp.height}",
+                        oninput: move |e: Event<FormData>| draft.write().height = e.value(),
+                    }
+                }
+
+                h3 { "レイヤー" }
+                div {
+                    class: "output-tabs dialog-tabs",
+                    for (index, layer) in comp.layers.iter().enumerate() {
+                        button {
+                            class: if index == layer_index { "output-tab active" } else { "output-tab" },
+                            onclick: move |_| current.set(index),
+                            if layer.name.is_empty() { "(名前なし)" } else { "{layer.name}" }
+                        }
+                    }
+                    button {
+                        class: "output-tab",
+                        onclick: move |_| {
+                            let mut draft = draft.write();
+                            let width = draft.width.trim().parse().unwrap_or(1920.0);
+                            let height = draft.height.trim().parse().unwrap_or(1080.0);
+                            let layer = MockLayer::new(&format!("Layer {}", draft.layers.len() + 1), width, height);
+                            draft.layers.push(LayerDraft {
+                                name: layer.name.clone(),
+                                properties: layer.properties.iter().map(PropertyDraft::from_property).collect(),
+                                effects: Vec::new(),
+                            });
+                            current.set(draft.layers.len() - 1);
+                        },
+                        "+"
+                    }
+                }
+
+                if let Some(layer) = layer {
+                    div {
+                        class: "dialog-row",
+                        label { "レイヤー名" }
+                        input {
+                            r#type: "text",
+                            value: "{layer.name}",
+                            oninput: move |e: Event<FormData>| draft.write().layers[layer_index].name = e.value(),
+                        }
+                        button {
+                            onclick: move |_| {
+                                draft.write().layers.remove(layer_index);
+                            },
+                            "レイヤーを削除"
+                        }
+                    }
+
+                    h3 { "プロパティ" }
+                    for (index, property) in layer.properties.into_iter().enumerate() {
+                        PropertyRow {
+                            key: "{layer_index}-p{index}",
+                            property,
+                            on_change: move |p: PropertyDraft| draft.write().layers[layer_index].properties[index] = p,
+                            on_remove: move |_| {
+                                draft.write().layers[layer_index].properties.remove(index);
+                            },
+                        }
+                    }
+                    button {
+                        onclick: move |_| draft.write().layers[layer_index].properties.push(PropertyDraft::new()),
+                        "+ プロパティ"
+                    }
+
+                    h3 { "エフェクト" }
+                    for (effect_index, effect) in layer.effects.into_iter().enumerate() {
+                        div {
+                            key: "{layer_index}-e{effect_index}",
+                            class: "comp-effect",
+                            div {
+                                class: "dialog-row",
+                                input {
+                                    r#type: "text",
+                                    placeholder: "エフェクト名",
+                                    value: "{effect.name}",
+                                    oninput: move |e: Event<FormData>| {
+                                        draft.write().layers[layer_index].effects[effect_index].name = e.value();
+                                    },
+                                }
+                                button {
+                                    onclick: move |_| {
+                                        draft.write().layers[layer_index].effects.remove(effect_index);
+                                    },
+                                    "削除"
+                                }
+                            }
+                            for (index, param) in effect.params.into_iter().enumerate() {
+                                PropertyRow {
+                                    key: "{layer_index}-e{effect_index}-{index}",
+                                    property: param,
+                                    on_change: move |p: PropertyDraft| {
+                                        draft.write().layers[layer_index].effects[effect_index].params[index] = p;
+                                    },
+                                    on_remove: move |_| {
+                                        draft.write().layers[layer_index].effects[effect_index].params.remove(index);
+                                    },
+                                }
+                            }
+                            button {
+                                onclick: move |_| {
+                                    draft.write().layers[layer_index].effects[effect_index].params.push(PropertyDraft::new());
+                                },
+                                "+ パラメーター"
+                            }
+                        }
+                    }
+                    button {
+                        onclick: move |_| {
+                            draft.write().layers[layer_index].effects.push(EffectDraft {
+                                name: "Slider Control".to_string(),
+                                params: vec![PropertyDraft {
+                                    name: "Slider".to_string(),
+                                    ..PropertyDraft::new()
+                                }],
+                            });
+                        },
+                        "+ エフェクト"
+                    }
+                }
+
+                if let Some(message) = error() {
+                    p { class: "dialog-error", "{message}" }
+                }
+
+                div {
+                    class: "dialog-actions",
+                    button { onclick: move |_| on_close.call(()), "キャンセル" }
+                    button {
+                        onclick: move |_| match draft.read().to_comp() {
+                            Ok(comp) => {
+                                app_state.write().comp = comp;
+                                on_close.call(());
+                            }
+                            Err(message) => error.set(Some(message)),
+                        },
+                        "保存"
+                    }
+                }
+            }
+        }
+    }
+}
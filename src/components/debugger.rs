@@ -0,0 +1,111 @@
+use dioxus::prelude::*;
+
+use crate::state::app_state::AppState;
+use crate::utils::debugger::Trace;
+use crate::utils::preview::{format_number, format_value};
+
+#[component]
+pub fn Debugger(trace: Trace, on_close: EventHandler<()>) -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    let mut step = use_signal(|| 0usize);
+    use_drop(move || app_state.write().debug_block = None);
+
+    let total = trace.steps.len();
+    let index = step().min(total);
+    let current = trace.steps.get(index).cloned();
+    let title = current
+        .as_ref()
+        .and_then(|s| app_state.read().get_block(&s.block_id).map(|b| b.title.clone()))
+        .unwrap_or_default();
+
+    // 最後のステップの次は実行結果の表示
+    let mut go = {
+        let trace = trace.clone();
+        move |index: usize| {
+            let index = index.min(trace.steps.len());
+            step.set(index);
+            app_state.write().debug_block = trace.steps.get(index).map(|s| s.block_id.clone());
+        }
+    };
+    let mut go_back = go.clone();
+    let mut go_next = go.clone();
+    let mut go_start = go.clone();
+    let next_break = {
+        let trace = trace.clone();
+        move || trace.next_break(index, &app_state.read().breakpoints)
+    };
+
+    rsx! {
+        div {
+            class: "debugger",
+
+            div {
+                class: "debugger-controls",
+                span {
+                    if current.is_some() { "ステップ {index + 1} / {total}" } else { "終了" }
+                    " ({format_number(trace.time)} 秒)"
+                }
+                button {
+                    class: "output-copy",
+                    disabled: index == 0,
+                    onclick: move |_| go_start(0),
+                    "最初から"
+                }
+                button {
+                    class: "output-copy",
+                    disabled: index == 0,
+                    onclick: move |_| go_back(index.saturating_sub(1)),
+                    "◀ 戻る"
+                }
+                button {
+                    class: "output-copy",
+                    disabled: current.is_none(),
+                    onclick: move |_| go_next(index + 1),
+                    "ステップ ▶"
+                }
+                button {
+                    class: "output-copy",
+                    disabled: current.is_none(),
+                    onclick: move |_| go(next_break()),
+                    "続行 ▶▶"
+                }
+                button { class: "output-copy", onclick: move |_| on_close.call(()), "閉じる" }
+            }
+
+            if let Some(current) = &current {
+                div {
+                    class: "debugger-step",
+                    p {
+                        class: "debugger-block",
+                        onclick: {
+                            let block_id = current.block_id.clone();
+                            move |_| app_state.write().selected = Some(block_id.clone())
+                        },
+                        "{title}"
+                    }
+                    if let Some(result) = &current.result {
+                        p { class: "preview-result", "= {format_value(result)}" }
+                    }
+                    if current.variables.is_empty() {
+                        p { class: "debugger-empty", "変数はありません" }
+                    } else {
+                        table {
+                            class: "debugger-variables",
+                            for (name, value) in current.variables.iter() {
+                                tr {
+                                    td { class: "debugger-name", "{name}" }
+                                    td { "{format_value(value)}" }
+                                }
+                            }
+                        }
+                    }
+                }
+            } else {
+                match &trace.outcome {
+                    Ok(value) => rsx! { p { class: "preview-result", "= {format_value(value)}" } },
+                    Err(e) => rsx! { p { class: "preview-error", "{e}" } },
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use dioxus::prelude::*;
+
+use crate::state::app_state::AppState;
+use crate::utils::lint::Diagnostic;
+
+#[component]
+pub fn Diagnostics(diagnostics: Vec<Diagnostic>) -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+
+    rsx! {
+        if diagnostics.is_empty() {
+            p { class: "output-empty", "問題は見つかりませんでした" }
+        } else {
+            ul {
+                class: "diagnostics",
+                for (index, diagnostic) in diagnostics.into_iter().enumerate() {
+                    li {
+                        key: "{index}",
+                        class: "diagnostic {diagnostic.severity.key()}",
+                        onclick: move |_| {
+                            let Some(block_id) = diagnostic.block_id.clone() else {
+                                return;
+                            };
+                            app_state.write().selected = Some(block_id.clone());
+                            // ボード上のブロックが見えるところまでスクロールする
+                            document::eval(&format!(
+                                "document.getElementById({:?})?.scrollIntoView({{ block: 'center', inline: 'center', behavior: 'smooth' }});",
+                                block_id
+                            ));
+                        },
+                        span { class: "diagnostic-icon", title: "{diagnostic.severity.label()}", "{diagnostic.severity.icon()}" }
+                        "{diagnostic.message}"
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use dioxus::prelude::*;
+
+use crate::models::block::BlockType;
+use crate::state::app_state::AppState;
+use crate::state::extract::ExtractParam;
+
+fn chosen(candidates: &Result<Vec<ExtractParam>, String>, checked: &[bool]) -> Vec<ExtractParam> {
+    match candidates {
+        Ok(candidates) => candidates
+            .iter()
+            .zip(checked)
+            .filter(|(_, checked)| **checked)
+            .map(|(param, _)| param.clone())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[component]
+pub fn ContextMenu(block_id: String, x: f64, y: f64, on_extract: EventHandler<String>) -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    // デバッガが止まるのは文のブロックだけ
+    let is_statement = app_state.read().get_block(&block_id).is_some_and(|b| b.block_type != BlockType::Value);
+    let has_breakpoint = app_state.read().breakpoints.contains(&block_id);
+
+    rsx! {
+        div {
+            class: "context-menu",
+            style: "left: {x}px; top: {y}px;",
+            onmousedown: move |e: MouseEvent| e.stop_propagation(),
+
+            button {
+                onclick: {
+                    let block_id = block_id.clone();
+                    move |_| {
+                        app_state.write().context_menu = None;
+                        on_extract.call(block_id.clone());
+                    }
+                },
+                "ブロックに切り出す"
+            }
+            if is_statement {
+                button {
+                    onclick: {
+                        let block_id = block_id.clone();
+                        move |_| {
+                            let mut state = app_state.write();
+                            state.context_menu = None;
+                            state.toggle_breakpoint(&block_id);
+                        }
+                    },
+                    if has_breakpoint { "ブレークポイントを解除" } else { "ブレークポイントを設定" }
+                }
+            }
+        }
+    }
+}
+
+// 書き換えられたフィールドを引数にして、スタックをカスタムブロックに置き換える
+#[component]
+pub fn ExtractDialog(block_id: String, on_close: EventHandler<()>) -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    let candidates = use_hook({
+        let block_id = block_id.clone();
+        move || app_state.read().extract_candidates(&block_id)
+    });
+    let mut title = use_signal({
+        let block_id = block_id.clone();
+        move || app_state.read().get_block(&block_id).map(|b| format!("{} など", b.title)).unwrap_or_default()
+    });
+    let count = candidates.as_ref().map_or(0, Vec::len);
+    let mut checked = use_signal(move || vec![true; count]);
+    let mut error = use_signal(|| None::<String>);
+
+    let params = chosen(&candidates, &checked());
+    // 引数の番号はチェックしたものだけで数える (テンプレートの {{paramN}} と揃える)
+    let numbers: Vec<Option<usize>> = checked()
+        .iter()
+        .scan(0, |count, checked| {
+            *count += usize::from(*checked);
+            Some(checked.then_some(*count))
+        })
+        .collect();
+    // 引数の選び方が変わったときだけ組み立て直す
+    let template = use_memo({
+        let block_id = block_id.clone();
+        let candidates = candidates.clone();
+        move || app_state.peek().extract_template(&block_id, &chosen(&candidates, &checked()))
+    });
+
+    rsx! {
+        div {
+            class: "dialog-backdrop",
+            onmousedown: move |e: MouseEvent| e.stop_propagation(),
+            onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+
+            div {
+                class: "dialog",
+
+                h2 { "ブロックに切り出す" }
+
+                match candidates.clone() {
+                    Err(message) => rsx! {
+                        p { class: "dialog-error", "{message}" }
+                        div {
+                            class: "dialog-actions",
+                            button { onclick: move |_| on_close.call(()), "閉じる" }
+                        }
+                    },
+                    Ok(candidates) => rsx! {
+                        div {
+                            class: "dialog-row",
+                            label { "タイトル" }
+                            input {
+                                r#type: "text",
+                                value: "{title}",
+                                oninput: move |e: Event<FormData>| title.set(e.value()),
+                            }
+                        }
+
+                        if candidates.is_empty() {
+                            p { class: "dialog-hint", "書き換えられたフィールドがないので、引数のないブロックになります" }
+                        } else {
+                            p { class: "dialog-hint", "チェックしたフィールドが引数になり、それ以外は出力に埋め込まれます" }
+                        }
+                        for (index, param) in candidates.iter().cloned().enumerate() {
+                            label {
+                                key: "{param.block_id}:{param.content_id}",
+                                class: "dialog-field",
+                                input {
+                                    r#type: "checkbox",
+                                    checked: checked()[index],
+                                    onchange: move |e: Event<FormData>| checked.write()[index] = e.checked(),
+                                }
+                                if let Some(number) = numbers[index] {
+                                    span { "param{number}" }
+                                } else {
+                                    span { "埋め込み" }
+                                }
+                                span { "{param.title}" }
+                                span {
+                                    class: "field-id",
+                                    if param.reporter.is_some() { "(値ブロック)" } else { "{param.value}" }
+                                }
+                            }
+                        }
+
+                        pre { class: "dialog-output", "{template}" }
+
+                        if let Some(message) = error() {
+                            p { class: "dialog-error", "{message}" }
+                        }
+
+                        div {
+                            class: "dialog-actions",
+                            button { onclick: move |_| on_close.call(()), "キャンセル" }
+                            button {
+                                onclick: {
+                                    let block_id = block_id.clone();
+                                    move |_| match app_state.write().extract_block(&block_id, &title(), &params) {
+                                        Ok(_) => on_close.call(()),
+                                        Err(message) => error.set(Some(message)),
+                                    }
+                                },
+                                "切り出す"
+                            }
+                        }
+                    },
+                }
+            }
+        }
+    }
+}
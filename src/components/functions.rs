@@ -0,0 +1,130 @@
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::models::block::ValueType;
+use crate::models::function::{Function, Parameter};
+use crate::state::app_state::AppState;
+
+#[component]
+pub fn FunctionsDialog(on_close: EventHandler<()>) -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    let mut draft = use_signal(|| app_state.read().functions.clone());
+    let mut error = use_signal(|| None::<String>);
+
+    rsx! {
+        div {
+            class: "dialog-backdrop",
+            onmousedown: move |e: MouseEvent| e.stop_propagation(),
+            onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+
+            div {
+                class: "dialog",
+
+                h2 { "マイブロック" }
+
+                if draft.read().is_empty() {
+                    p { class: "dialog-hint", "ブロックを作ると定義ブロックがワークスペースに置かれ、呼び出しのブロックがパレットの「マイブロック」に並びます" }
+                }
+
+                for (index, function) in draft().into_iter().enumerate() {
+                    div {
+                        key: "{function.id}",
+                        class: "dialog-section",
+
+                        div {
+                            class: "dialog-field",
+                            input {
+                                r#type: "text",
+                                placeholder: "ブロック名",
+                                value: "{function.name}",
+                                oninput: move |e: Event<FormData>| draft.write()[index].name = e.value().trim().to_string(),
+                            }
+                            button {
+                                onclick: move |_| {
+                                    let mut draft = draft.write();
+                                    let params = &mut draft[index].params;
+                                    let name = (1..).map(|n| format!("arg{}", n)).find(|name| !params.iter().any(|p| &p.name == name)).unwrap_or_default();
+                                    params.push(Parameter {
+                                        id: Uuid::new_v4().simple().to_string(),
+                                        name,
+                                        value_type: ValueType::Any,
+                                    });
+                                },
+                                "+ 引数"
+                            }
+                            button {
+                                onclick: move |_| {
+                                    draft.write().remove(index);
+                                },
+                                "削除"
+                            }
+                        }
+
+                        for (param_index, param) in function.params.iter().cloned().enumerate() {
+                            div {
+                                key: "{param.id}",
+                                class: "dialog-field dialog-param",
+
+                                input {
+                                    r#type: "text",
+                                    placeholder: "引数名",
+                                    value: "{param.name}",
+                                    oninput: move |e: Event<FormData>| draft.write()[index].params[param_index].name = e.value().trim().to_string(),
+                                }
+                                select {
+                                    onchange: move |e: Event<FormData>| {
+                                        if let Some(t) = ValueType::all().into_iter().find(|t| format!("{:?}", t) == e.value()) {
+                                            draft.write()[index].params[param_index].value_type = t;
+                                        }
+                                    },
+                                    for t in ValueType::all() {
+                                        option {
+                                            value: "{t:?}",
+                                            selected: t == param.value_type,
+                                            "{t.label()}"
+                                        }
+                                    }
+                                }
+                                button {
+                                    onclick: move |_| {
+                                        draft.write()[index].params.remove(param_index);
+                                    },
+                                    "削除"
+                                }
+                            }
+                        }
+                    }
+                }
+
+                button {
+                    onclick: move |_| {
+                        let mut draft = draft.write();
+                        let name = (1..).map(|n| format!("block{}", n)).find(|name| !draft.iter().any(|f| &f.name == name)).unwrap_or_default();
+                        draft.push(Function {
+                            id: Uuid::new_v4().simple().to_string(),
+                            name,
+                            params: Vec::new(),
+                        });
+                    },
+                    "+ ブロックを作る"
+                }
+
+                if let Some(message) = error() {
+                    p { class: "dialog-error", "{message}" }
+                }
+
+                div {
+                    class: "dialog-actions",
+                    button { onclick: move |_| on_close.call(()), "キャンセル" }
+                    button {
+                        onclick: move |_| match app_state.write().set_functions(draft()) {
+                            Ok(()) => on_close.call(()),
+                            Err(message) => error.set(Some(message)),
+                        },
+                        "保存"
+                    }
+                }
+            }
+        }
+    }
+}
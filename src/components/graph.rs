@@ -0,0 +1,130 @@
+use dioxus::prelude::*;
+
+use crate::utils::preview::{format_number, Sample};
+
+const GRAPH_WIDTH: f64 = 360.0;
+const GRAPH_HEIGHT: f64 = 120.0;
+const GRAPH_PADDING: f64 = 8.0;
+const CURVE_COLORS: [&str; 4] = ["#4C97FF", "#FF4D8D", "#59C059", "#FFAB19"];
+
+fn value_range(samples: &[Sample]) -> (f64, f64) {
+    let values = samples.iter().flat_map(|s| s.values.iter().copied()).filter(|v| v.is_finite());
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| (min.min(v), max.max(v)));
+    if min > max {
+        (-1.0, 1.0)
+    } else if min == max {
+        (min - 1.0, max + 1.0)
+    } else {
+        (min, max)
+    }
+}
+
+fn nearest(samples: &[Sample], time: f64) -> Option<&Sample> {
+    samples
+        .iter()
+        .min_by(|a, b| (a.time - time).abs().total_cmp(&(b.time - time).abs()))
+}
+
+#[component]
+pub fn ValueGraph(samples: Vec<Sample>, duration: f64, time: f64, on_scrub: EventHandler<f64>) -> Element {
+    let mut dragging = use_signal(|| false);
+    let duration = if duration > 0.0 { duration } else { 1.0 };
+    let (min, max) = value_range(&samples);
+    let to_x = move |t: f64| t / duration * GRAPH_WIDTH;
+    let to_y = move |v: f64| GRAPH_PADDING + (max - v) / (max - min) * (GRAPH_HEIGHT - GRAPH_PADDING * 2.0);
+
+    let dimensions = samples.iter().map(|s| s.values.len()).max().unwrap_or(0);
+    let curves: Vec<(String, &str)> = (0..dimensions)
+        .map(|d| {
+            let points = samples
+                .iter()
+                .filter_map(|s| s.values.get(d).filter(|v| v.is_finite()).map(|v| format!("{:.2},{:.2}", to_x(s.time), to_y(*v))))
+                .collect::<Vec<_>>()
+                .join(" ");
+            (points, CURVE_COLORS[d % CURVE_COLORS.len()])
+        })
+        .collect();
+    let cursor_x = to_x(time.clamp(0.0, duration));
+    let current = nearest(&samples, time).cloned();
+
+    // カーソル位置に最も近いサンプルの時間へ合わせる
+    let scrub = {
+        let samples = samples.clone();
+        move |x: f64| {
+            let t = (x / GRAPH_WIDTH * duration).clamp(0.0, duration);
+            if let Some(sample) = nearest(&samples, t) {
+                on_scrub.call(sample.time);
+            }
+        }
+    };
+    let scrub_down = scrub.clone();
+    let scrub_move = scrub;
+
+    rsx! {
+        div {
+            class: "value-graph",
+
+            svg {
+                width: "{GRAPH_WIDTH}",
+                height: "{GRAPH_HEIGHT}",
+                view_box: "0 0 {GRAPH_WIDTH} {GRAPH_HEIGHT}",
+                onmousedown: move |e: MouseEvent| {
+                    e.stop_propagation();
+                    dragging.set(true);
+                    scrub_down(e.element_coordinates().x);
+                },
+                onmousemove: move |e: MouseEvent| {
+                    if dragging() {
+                        scrub_move(e.element_coordinates().x);
+                    }
+                },
+                onmouseup: move |_| dragging.set(false),
+                onmouseleave: move |_| dragging.set(false),
+
+                if min < 0.0 && max > 0.0 {
+                    line {
+                        class: "value-graph-zero",
+                        x1: "0",
+                        y1: "{to_y(0.0)}",
+                        x2: "{GRAPH_WIDTH}",
+                        y2: "{to_y(0.0)}",
+                    }
+                }
+                for (points, color) in curves {
+                    polyline {
+                        points: "{points}",
+                        fill: "none",
+                        stroke: "{color}",
+                        stroke_width: "1.5",
+                    }
+                }
+                line {
+                    class: "value-graph-cursor",
+                    x1: "{cursor_x}",
+                    y1: "0",
+                    x2: "{cursor_x}",
+                    y2: "{GRAPH_HEIGHT}",
+                }
+            }
+
+            div {
+                class: "value-graph-legend",
+                span { "{format_number(max)}" }
+                span { "{format_number(min)}" }
+            }
+
+            if let Some(current) = current {
+                div {
+                    class: "value-graph-readout",
+                    span { "{format_number(current.time)} 秒" }
+                    for (index, value) in current.values.iter().enumerate() {
+                        span {
+                            style: "color: {CURVE_COLORS[index % CURVE_COLORS.len()]}",
+                            "{format_number(*value)}"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use dioxus::prelude::*;
+
+use crate::models::block::BlockType;
+use crate::state::app_state::AppState;
+
+#[component]
+pub fn ImportDialog(on_close: EventHandler<()>) -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    let mut source = use_signal(String::new);
+
+    let on_import = move |_| {
+        let mut state = app_state.write();
+        let ids = state.import_expression(&source());
+        let raw = ids
+            .iter()
+            .filter(|id| state.get_block(id).is_some_and(|b| b.block_type == BlockType::Raw))
+            .count();
+        if raw == 0 {
+            state.notify(format!("{} 個のブロックを読み込みました", ids.len()));
+        } else {
+            state.notify(format!("{} 個のブロックを読み込みました (コードのまま: {})", ids.len(), raw));
+        }
+        on_close.call(());
+    };
+
+    rsx! {
+        div {
+            class: "dialog-backdrop",
+            onmousedown: move |e: MouseEvent| e.stop_propagation(),
+            onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+
+            div {
+                class: "dialog",
+
+                h2 { "式の読み込み" }
+                p {
+                    class: "dialog-message",
+                    "ライブラリのブロックに一致しない部分はコードブロックとして読み込みます"
+                }
+
+                textarea {
+                    class: "dialog-output dialog-source",
+                    placeholder: "wiggle(2, 30)",
+                    value: "{source}",
+                    oninput: move |e: Event<FormData>| source.set(e.value()),
+                }
+
+                div {
+                    class: "dialog-actions",
+                    button { onclick: move |_| on_close.call(()), "閉じる" }
+                    button {
+                        disabled: source().trim().is_empty(),
+                        onclick: on_import,
+                        "読み込む"
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,19 @@
 pub mod block;
+pub mod block_editor;
 pub mod board;
+pub mod code_editor;
+pub mod comp_editor;
+pub mod debugger;
+pub mod diagnostics;
+pub mod extract;
+pub mod functions;
+pub mod graph;
+pub mod import;
 pub mod output;
+pub mod preview;
+pub mod settings;
+pub mod share;
 pub mod sidebar;
+pub mod slot;
+pub mod toast;
+pub mod variables;
@@ -1,10 +1,266 @@
 use dioxus::prelude::*;
 
+use crate::models::block::TargetProperty;
+use crate::state::app_state::AppState;
+use crate::utils::clipboard;
+use crate::utils::generator;
+use crate::utils::highlight::highlight_lines;
+use crate::utils::lint;
+
 #[component]
 pub fn Output() -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    let mut show_problems = use_signal(|| false);
+    // 診断はつながりや入力値が変わったときだけやり直す (ドラッグ中の移動では変わらない)
+    let fingerprint = use_memo(move || lint::fingerprint(&app_state.read()));
+    let diagnostics = use_memo(move || {
+        fingerprint();
+        lint::lint(&app_state.peek())
+    });
+    let state = app_state.read();
+    let flags = generator::flag_ids(&state);
+    let active = state.active_flag();
+    let tabs: Vec<(String, String)> = flags
+        .iter()
+        .enumerate()
+        .filter_map(|(index, id)| {
+            state
+                .get_block(id)
+                .map(|b| (id.clone(), format!("{} {}", b.title, index + 1)))
+        })
+        .collect();
+    let generated = active.as_ref().and_then(|id| generator::generate(&state, id));
+    let evaluation_error = active.as_ref().and_then(|id| state.evaluation_errors.get(id).cloned());
+    let unused = generator::unused_functions(&state);
+    let diagnostics = diagnostics();
+    let worst = diagnostics.first().map(|d| d.severity);
+    let selected_lines = match (&generated, &state.selected) {
+        (Some(generated), Some(selected)) => generated.lines_for_block(selected),
+        _ => Vec::new(),
+    };
+    let debug_lines = match (&generated, &state.debug_block) {
+        (Some(generated), Some(debug)) => generated.lines_for_block(debug),
+        _ => Vec::new(),
+    };
+    // ブレークポイントはブロックの先頭行に表示する
+    let breakpoint_lines: Vec<usize> = match &generated {
+        Some(generated) => state
+            .breakpoints
+            .iter()
+            .filter_map(|id| generated.lines_for_block(id).first().copied())
+            .collect(),
+        None => Vec::new(),
+    };
+    drop(state);
+
+    let lines = generated
+        .as_ref()
+        .map(|g| highlight_lines(&g.code))
+        .unwrap_or_default();
+
     rsx! (
         div {
             id: "output",
+
+            div {
+                class: "output-tabs",
+                for (id, label) in tabs {
+                    button {
+                        class: if !show_problems() && Some(&id) == active.as_ref() { "output-tab active" } else { "output-tab" },
+                        onclick: {
+                            let id = id.clone();
+                            move |_| {
+                                show_problems.set(false);
+                                app_state.write().active_output = Some(id.clone());
+                            }
+                        },
+                        "⚑ {label}"
+                    }
+                }
+                button {
+                    class: if show_problems() { "output-tab problems active" } else { "output-tab problems" },
+                    onclick: move |_| show_problems.set(true),
+                    if let Some(worst) = worst {
+                        span { class: "diagnostic {worst.key()}", "{worst.icon()} " }
+                    }
+                    "問題 {diagnostics.len()}"
+                }
+            }
+
+            if show_problems() {
+                crate::components::diagnostics::Diagnostics { diagnostics }
+            } else if let Some(generated) = generated {
+                div {
+                    class: "output-header",
+                    span { "⚑ {generated.flag_title}" }
+                    select {
+                        class: "output-target",
+                        title: "貼り付け先のプロパティ",
+                        onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+                        onchange: {
+                            let flag_id = generated.flag_id.clone();
+                            let current = generated.target.clone();
+                            move |e: Event<FormData>| {
+                                let target = TargetProperty::all().into_iter().find(|t| t.key() == e.value()).map(|t| {
+                                    match (&t, &current) {
+                                        // カスタムどうしの切り替えでは次元を保つ
+                                        (TargetProperty::Custom { .. }, Some(TargetProperty::Custom { .. })) => current.clone().unwrap_or(t),
+                                        _ => t,
+                                    }
+                                });
+                                app_state.write().set_flag_target(&flag_id, target);
+                            }
+                        },
+                        option { value: "", selected: generated.target.is_none(), "(プロパティ未指定)" }
+                        for target in TargetProperty::all() {
+                            option {
+                                value: target.key(),
+                                selected: generated.target.as_ref().map(|t| t.key()) == Some(target.key()),
+                                if matches!(target, TargetProperty::Custom { .. }) { "カスタム" } else { "{target.label()}" }
+                            }
+                        }
+                    }
+                    if let Some(TargetProperty::Custom { dimension }) = generated.target.clone() {
+                        input {
+                            class: "output-dimension",
+                            r#type: "number",
+                            min: "1",
+                            max: "4",
+                            title: "次元",
+                            value: "{dimension}",
+                            onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+                            onchange: {
+                                let flag_id = generated.flag_id.clone();
+                                move |e: Event<FormData>| {
+                                    if let Ok(dimension) = e.value().trim().parse::<usize>() {
+                                        let dimension = dimension.clamp(1, 4);
+                                        app_state.write().set_flag_target(&flag_id, Some(TargetProperty::Custom { dimension }));
+                                    }
+                                }
+                            },
+                        }
+                    }
+                    if let Some(target) = &generated.target {
+                        span { class: "output-location", "貼り付け先: {target.location()}" }
+                    }
+                    button {
+                        class: "output-copy",
+                        onclick: {
+                            let code = generated.code.clone();
+                            move |_| {
+                                let message = match clipboard::set_text(&code) {
+                                    Ok(()) => "クリップボードにコピーしました".to_string(),
+                                    Err(e) => format!("コピーに失敗しました: {}", e),
+                                };
+                                app_state.write().notify(message);
+                            }
+                        },
+                        "コピー"
+                    }
+                }
+
+                div {
+                    class: "output-code",
+                    onmouseleave: move |_| app_state.write().highlighted_block = None,
+                    for (number, line) in lines.into_iter().enumerate() {
+                        div {
+                            class: format!(
+                                "output-line{}{}",
+                                if selected_lines.contains(&number) { " selected" } else { "" },
+                                if debug_lines.contains(&number) { " debug" } else { "" },
+                            ),
+                            onmouseenter: {
+                                let block_id = generated.block_at_line(number).map(str::to_string);
+                                move |_| {
+                                    if app_state.read().highlighted_block != block_id {
+                                        app_state.write().highlighted_block = block_id.clone();
+                                    }
+                                }
+                            },
+                            onclick: {
+                                let block_id = generated.block_at_line(number).map(str::to_string);
+                                move |_| {
+                                    if let Some(block_id) = &block_id {
+                                        app_state.write().selected = Some(block_id.clone());
+                                    }
+                                }
+                            },
+                            span {
+                                class: if breakpoint_lines.contains(&number) { "line-number breakpoint" } else { "line-number" },
+                                title: "クリックでブレークポイントを切り替え",
+                                onclick: {
+                                    let block_id = generated.block_at_line(number).map(str::to_string);
+                                    move |e: MouseEvent| {
+                                        e.stop_propagation();
+                                        if let Some(block_id) = &block_id {
+                                            app_state.write().toggle_breakpoint(block_id);
+                                        }
+                                    }
+                                },
+                                "{number + 1}"
+                            }
+                            span {
+                                class: "line-code",
+                                for span in line {
+                                    span { class: span.kind.class(), "{span.text}" }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // 貼り付け先が変わったら元の値を既定値に戻す
+                crate::components::preview::Preview {
+                    key: "{generated.flag_id}:{generated.target.as_ref().map(|t| t.label()).unwrap_or_default()}",
+                    generated: generated.clone(),
+                }
+
+                if !generated.errors.is_empty() || !generated.warnings.is_empty() || evaluation_error.is_some() || !unused.is_empty() {
+                    ul {
+                        class: "output-errors",
+                        if let Some(message) = evaluation_error {
+                            li {
+                                onclick: {
+                                    let flag_id = generated.flag_id.clone();
+                                    move |_| app_state.write().selected = Some(flag_id.clone())
+                                },
+                                "⚠ {message}"
+                            }
+                        }
+                        for error in generated.errors {
+                            li {
+                                onclick: {
+                                    let block_id = error.block_id.clone();
+                                    move |_| app_state.write().selected = Some(block_id.clone())
+                                },
+                                "{error.message}"
+                            }
+                        }
+                        for warning in generated.warnings {
+                            li {
+                                class: "output-note",
+                                onclick: {
+                                    let block_id = warning.block_id.clone();
+                                    move |_| app_state.write().selected = Some(block_id.clone())
+                                },
+                                "⚠ {warning.message}"
+                            }
+                        }
+                        for error in unused {
+                            li {
+                                class: "output-note",
+                                onclick: {
+                                    let block_id = error.block_id.clone();
+                                    move |_| app_state.write().selected = Some(block_id.clone())
+                                },
+                                "{error.message}"
+                            }
+                        }
+                    }
+                }
+            } else {
+                p { class: "output-empty", "旗ブロックを置くと、ここに出力が表示されます" }
+            }
         }
     )
-}
\ No newline at end of file
+}
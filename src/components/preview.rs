@@ -0,0 +1,201 @@
+use dioxus::prelude::*;
+
+use crate::components::debugger::Debugger;
+use crate::components::graph::ValueGraph;
+use crate::state::app_state::AppState;
+use crate::utils::debugger::{self, Trace};
+use crate::utils::generator::GeneratedCode;
+use crate::utils::preview::{self, PreviewInput};
+use crate::utils::sandbox;
+
+#[component]
+pub fn Preview(generated: GeneratedCode) -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    let code = generated.code.clone();
+    // 入力欄は文字列のまま持ち、解釈できたものを AppState の preview に反映する
+    let mut time = use_signal(|| preview::format_number(app_state.read().preview.time));
+    let mut value = use_signal(|| preview::format_value(&app_state.read().preview_value_for(&generated.flag_id)));
+    let mut result = use_signal(|| None::<Result<String, String>>);
+    let mut busy = use_signal(|| false);
+    let this_layer = app_state.read().preview.this_layer.clone();
+    let mut show_graph = use_signal(|| false);
+    // 記録したときのコードと一緒に持ち、コードが変わったら捨てる
+    let mut trace = use_signal(|| None::<(String, Trace)>);
+    let mut trace_run = use_signal(|| 0u64);
+    let comp = app_state.read().comp.clone();
+    let layer_names: Vec<String> = comp.layers.iter().map(|l| l.name.clone()).collect();
+    let duration = comp.duration;
+
+    // 式やコンポジションが変わったときだけ全フレームを評価し直す
+    // 評価は別プロセスで行い、入力が変わったら前の評価は取り消される
+    let samples = use_resource(use_reactive(
+        (&code, &comp, &value(), &this_layer, &show_graph(), &generated.flag_id),
+        move |(code, comp, value, this_layer, show_graph, flag_id)| async move {
+            if !show_graph {
+                return None;
+            }
+            let Some(value) = preview::parse_value(&value) else {
+                return Some(Err("元の値は 0 や [960, 540] の形式で入力してください".to_string()));
+            };
+            let input = PreviewInput {
+                time: 0.0,
+                value,
+                comp,
+                this_layer,
+            };
+            let sampled = sandbox::sample(code, input).await;
+            app_state.write().report_evaluation(&flag_id, sampled.as_ref().err());
+            Some(sampled.map_err(|e| e.to_string()))
+        },
+    ));
+    let cursor = time().trim().parse::<f64>().unwrap_or(0.0);
+
+    let read_input = move || {
+        let Ok(time) = time().trim().parse::<f64>() else {
+            return Err("時間には数値を入力してください".to_string());
+        };
+        let Some(value) = preview::parse_value(&value()) else {
+            return Err("元の値は 0 や [960, 540] の形式で入力してください".to_string());
+        };
+        Ok(PreviewInput {
+            time,
+            value,
+            comp: app_state.read().comp.clone(),
+            this_layer: app_state.read().preview.this_layer.clone(),
+        })
+    };
+
+    let evaluate = {
+        let code = code.clone();
+        let flag_id = generated.flag_id.clone();
+        move |_| match read_input() {
+            Ok(input) => {
+                let code = code.clone();
+                let flag_id = flag_id.clone();
+                busy.set(true);
+                spawn(async move {
+                    let evaluated = sandbox::evaluate(code, input).await;
+                    app_state.write().report_evaluation(&flag_id, evaluated.as_ref().err());
+                    result.set(Some(evaluated.map(|v| preview::format_value(&v)).map_err(|e| e.to_string())));
+                    busy.set(false);
+                });
+            }
+            Err(message) => result.set(Some(Err(message))),
+        }
+    };
+
+    let debug = {
+        let generated = generated.clone();
+        move |_| match read_input() {
+            Ok(input) => {
+                let generated = generated.clone();
+                busy.set(true);
+                spawn(async move {
+                    let recorded = debugger::trace(&generated, input).await;
+                    let mut state = app_state.write();
+                    state.report_evaluation(&generated.flag_id, recorded.outcome.as_ref().err());
+                    state.debug_block = recorded.steps.first().map(|s| s.block_id.clone());
+                    drop(state);
+                    trace.set(Some((generated.code.clone(), recorded)));
+                    trace_run += 1;
+                    busy.set(false);
+                });
+            }
+            Err(message) => result.set(Some(Err(message))),
+        }
+    };
+    let current_trace = trace().filter(|(traced, _)| *traced == code).map(|(_, t)| t);
+
+    rsx! {
+        div {
+            class: "preview",
+
+            div {
+                class: "preview-inputs",
+                label { "時間 (秒)" }
+                input {
+                    r#type: "number",
+                    step: "0.1",
+                    value: "{time}",
+                    onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+                    oninput: move |e: Event<FormData>| {
+                        if let Ok(t) = e.value().trim().parse::<f64>() {
+                            app_state.write().preview.time = t;
+                        }
+                        time.set(e.value());
+                    },
+                }
+                label { "元の値" }
+                input {
+                    r#type: "text",
+                    value: "{value}",
+                    onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+                    oninput: move |e: Event<FormData>| {
+                        if let Some(v) = preview::parse_value(&e.value()) {
+                            app_state.write().preview.value = v;
+                        }
+                        value.set(e.value());
+                    },
+                }
+                label { "thisLayer" }
+                select {
+                    onchange: move |e: Event<FormData>| {
+                        let name = e.value();
+                        app_state.write().preview.this_layer = if name.is_empty() { None } else { Some(name) };
+                    },
+                    option { value: "", selected: this_layer.is_none(), "(既定)" }
+                    for name in layer_names {
+                        option {
+                            value: "{name}",
+                            selected: this_layer.as_ref() == Some(&name),
+                            "{name}"
+                        }
+                    }
+                }
+                button { class: "output-copy", disabled: busy(), onclick: evaluate, "評価" }
+                button {
+                    class: if show_graph() { "output-copy active" } else { "output-copy" },
+                    onclick: move |_| show_graph.toggle(),
+                    "グラフ"
+                }
+                button { class: "output-copy", disabled: busy(), onclick: debug, "デバッグ" }
+            }
+
+            if busy() {
+                p { class: "preview-pending", "評価中…" }
+            }
+            match result() {
+                Some(Ok(text)) => rsx! { p { class: "preview-result", "= {text}" } },
+                Some(Err(message)) => rsx! { p { class: "preview-error", "{message}" } },
+                None => rsx! {},
+            }
+
+            if let Some(current_trace) = current_trace {
+                Debugger {
+                    key: "{trace_run}",
+                    trace: current_trace,
+                    on_close: move |_| trace.set(None),
+                }
+            }
+
+            match samples.cloned() {
+                Some(None) | None if !show_graph() => rsx! {},
+                None => rsx! { p { class: "preview-pending", "評価中…" } },
+                Some(Some(Ok(samples))) => rsx! {
+                    ValueGraph {
+                        samples,
+                        duration,
+                        time: cursor,
+                        on_scrub: move |t: f64| {
+                            app_state.write().preview.time = t;
+                            time.set(preview::format_number(t));
+                            result.set(None);
+                        },
+                    }
+                },
+                Some(Some(Err(message))) => rsx! { p { class: "preview-error", "{message}" } },
+                Some(None) => rsx! {},
+            }
+        }
+    }
+}
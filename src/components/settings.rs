@@ -0,0 +1,65 @@
+use dioxus::prelude::*;
+
+use crate::state::app_state::AppState;
+use crate::state::settings::FlagAction;
+use crate::utils::sandbox;
+
+#[component]
+pub fn SettingsDialog(on_close: EventHandler<()>) -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    let mut settings = use_signal(|| app_state.read().settings.clone());
+
+    rsx! {
+        div {
+            class: "dialog-backdrop",
+            onmousedown: move |e: MouseEvent| e.stop_propagation(),
+            onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+
+            div {
+                class: "dialog",
+
+                h2 { "設定" }
+
+                h3 { "旗ボタンを押したとき" }
+                for action in FlagAction::all() {
+                    label {
+                        class: "dialog-row",
+                        input {
+                            r#type: "radio",
+                            name: "flag-action",
+                            checked: settings.read().flag_action == action,
+                            onchange: move |_| settings.write().flag_action = action,
+                        }
+                        "{action.label()}"
+                    }
+                }
+
+                h3 { "表示" }
+                label {
+                    class: "dialog-row",
+                    input {
+                        r#type: "checkbox",
+                        checked: settings.read().show_code_editor,
+                        onchange: move |e: Event<FormData>| settings.write().show_code_editor = e.checked(),
+                    }
+                    "ボードの横にテキストエディタを表示"
+                }
+
+                h3 { "式の評価" }
+                p { class: "dialog-note", "{sandbox::limits_description()}" }
+
+                div {
+                    class: "dialog-actions",
+                    button { onclick: move |_| on_close.call(()), "キャンセル" }
+                    button {
+                        onclick: move |_| {
+                            app_state.write().save_settings(settings());
+                            on_close.call(());
+                        },
+                        "保存"
+                    }
+                }
+            }
+        }
+    }
+}
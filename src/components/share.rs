@@ -0,0 +1,203 @@
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::state::app_state::AppState;
+use crate::utils::share::{self, SharePayload};
+use crate::utils::{clipboard, export, storage};
+
+const IMPORT_POSITION: (f64, f64) = (300.0, 80.0);
+const PNG_SCALE: f64 = 2.0;
+
+#[derive(Clone, Copy, PartialEq)]
+enum ShareTarget {
+    Stack,
+    Project,
+}
+
+#[component]
+pub fn ShareDialog(on_close: EventHandler<()>) -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    let mut code = use_signal(String::new);
+    let mut import_text = use_signal(String::new);
+    let mut message = use_signal(|| None::<String>);
+    // プロジェクトの共有コードはワークスペースを置き換えるので確認してから読み込む
+    let mut pending_project = use_signal(|| None::<SharePayload>);
+
+    let has_selection = app_state.read().selected.is_some();
+
+    let mut generate = move |target: ShareTarget| {
+        let state = app_state.read();
+        let payload = match target {
+            ShareTarget::Stack => match &state.selected {
+                Some(selected) => SharePayload::Stack(state.copy_stack(selected)),
+                None => return,
+            },
+            ShareTarget::Project => SharePayload::Project(state.to_project()),
+        };
+        match share::encode(&payload) {
+            Ok(encoded) => {
+                code.set(encoded);
+                message.set(None);
+            }
+            Err(e) => message.set(Some(e.to_string())),
+        }
+    };
+
+    let mut export_image = move |target: ShareTarget, extension: &str| {
+        let state = app_state.read();
+        let blocks = match target {
+            ShareTarget::Stack => match &state.selected {
+                Some(selected) => state.copy_stack(selected),
+                None => return,
+            },
+            ShareTarget::Project => state.blocks.values().cloned().collect(),
+        };
+        if blocks.is_empty() {
+            return;
+        }
+        let result = storage::export_dir().map_err(export::ExportError::Io).and_then(|dir| {
+            let path = dir.join(format!("blocks_{}.{}", Uuid::new_v4().simple(), extension));
+            export::save(&blocks, &path, PNG_SCALE).map(|_| path)
+        });
+        match result {
+            Ok(path) => message.set(Some(format!("書き出しました: {}", path.display()))),
+            Err(e) => message.set(Some(format!("書き出しに失敗しました: {}", e))),
+        }
+    };
+
+    let mut apply = move |payload: SharePayload| {
+        let missing = app_state.write().import_share(payload, IMPORT_POSITION);
+        if missing.is_empty() {
+            message.set(Some("読み込みました".to_string()));
+        } else {
+            let names = missing
+                .iter()
+                .map(|d| format!("{} (v{})", d.id, d.version))
+                .collect::<Vec<_>>()
+                .join(", ");
+            message.set(Some(format!("読み込みました。不足している定義: {}", names)));
+        }
+        import_text.set(String::new());
+    };
+
+    let on_import = move |_| {
+        let payload = match share::decode(&import_text()) {
+            Ok(payload) => payload,
+            Err(e) => {
+                message.set(Some(e.to_string()));
+                return;
+            }
+        };
+        match payload {
+            SharePayload::Project(_) if !app_state.read().blocks.is_empty() => pending_project.set(Some(payload)),
+            payload => apply(payload),
+        }
+    };
+
+    rsx! {
+        div {
+            class: "dialog-backdrop",
+            onmousedown: move |e: MouseEvent| e.stop_propagation(),
+            onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+
+            div {
+                class: "dialog",
+
+                h2 { "共有" }
+
+                div {
+                    class: "dialog-row",
+                    button {
+                        disabled: !has_selection,
+                        onclick: move |_| generate(ShareTarget::Stack),
+                        "選択中のスタック"
+                    }
+                    button {
+                        onclick: move |_| generate(ShareTarget::Project),
+                        "プロジェクト全体"
+                    }
+                }
+
+                if !code().is_empty() {
+                    textarea {
+                        class: "dialog-output",
+                        readonly: true,
+                        value: "{code}",
+                    }
+                    div {
+                        class: "dialog-actions",
+                        button {
+                            onclick: move |_| {
+                                if let Err(e) = clipboard::set_text(&code()) {
+                                    message.set(Some(format!("コピーに失敗しました: {}", e)));
+                                }
+                            },
+                            "コピー"
+                        }
+                    }
+                }
+
+                h3 { "画像として書き出す" }
+                div {
+                    class: "dialog-row",
+                    button {
+                        disabled: !has_selection,
+                        onclick: move |_| export_image(ShareTarget::Stack, "svg"),
+                        "スタック (SVG)"
+                    }
+                    button {
+                        disabled: !has_selection,
+                        onclick: move |_| export_image(ShareTarget::Stack, "png"),
+                        "スタック (PNG)"
+                    }
+                    button {
+                        onclick: move |_| export_image(ShareTarget::Project, "svg"),
+                        "全体 (SVG)"
+                    }
+                    button {
+                        onclick: move |_| export_image(ShareTarget::Project, "png"),
+                        "全体 (PNG)"
+                    }
+                }
+
+                h3 { "共有コードの読み込み" }
+                textarea {
+                    class: "dialog-output",
+                    placeholder: "{share::SHARE_PREFIX}...",
+                    value: "{import_text}",
+                    oninput: move |e: Event<FormData>| import_text.set(e.value()),
+                }
+
+                if let Some(text) = message() {
+                    p { class: "dialog-message", "{text}" }
+                }
+
+                if pending_project.read().is_some() {
+                    p { class: "dialog-error", "現在のワークスペースのブロックはすべて置き換えられます。読み込みますか？" }
+                    div {
+                        class: "dialog-actions",
+                        button { onclick: move |_| pending_project.set(None), "やめる" }
+                        button {
+                            onclick: move |_| {
+                                if let Some(payload) = pending_project.take() {
+                                    apply(payload);
+                                }
+                            },
+                            "置き換えて読み込む"
+                        }
+                    }
+                }
+
+                div {
+                    class: "dialog-actions",
+                    button { onclick: move |_| on_close.call(()), "閉じる" }
+                    button {
+                        disabled: import_text().trim().is_empty(),
+                        onclick: on_import,
+                        "読み込む"
+                    }
+                }
+            }
+        }
+    }
+}
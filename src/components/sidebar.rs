@@ -1,19 +1,57 @@
 use dioxus::html::input::placeholder;
 use dioxus::prelude::*;
 use crate::state::app_state::{AppState, BlockList};
-use crate::models::block::{Block, BlockContent, BlockType, Connection, EnumBlockContent, ContentValue, Position, Size};
+use crate::state::builtin;
+use crate::models::block::{Block, BlockContent, BlockType, Connection, EnumBlockContent, ContentValue, Position, Size, ValueType};
 use crate::components::block::{DraggableBlock, BlockData};
+use crate::components::block_editor::BlockEditor;
+use crate::components::settings::SettingsDialog;
+use crate::components::functions::FunctionsDialog;
+use crate::components::variables::VariablesDialog;
+use crate::components::comp_editor::CompDialog;
+use crate::components::import::ImportDialog;
+use crate::components::share::ShareDialog;
+
+#[derive(Clone, PartialEq)]
+enum PaletteTab {
+    All,
+    Favorites,
+    Recent,
+    Type(BlockType),
+    Category(String),
+}
 
 #[component]
 pub fn Sidebar() -> Element {
     let mut app_state = use_context::<Signal<AppState>>();
+    let mut show_editor = use_signal(|| false);
+    let mut editing = use_signal(|| None::<BlockList>);
+    let mut query = use_signal(String::new);
+    let mut tab = use_signal(|| PaletteTab::All);
+    let mut show_share = use_signal(|| false);
+    let mut show_settings = use_signal(|| false);
+    let mut show_import = use_signal(|| false);
+    let mut show_comp = use_signal(|| false);
+    let mut show_variables = use_signal(|| false);
+    let mut show_functions = use_signal(|| false);
 
     use_effect(move || {
         let mut state = app_state.write();
 
-        state.add_block_list(BlockList {
-            name: "block_1".to_string(),
-            block: Block {
+        state.add_block_list(BlockList::new(
+            "builtin",
+            "flag",
+            1,
+            Block {
+                connection: Connection::Output,
+                ..Block::new(String::new(), BlockType::Flag, "Flag".to_string(), Position { x: 0.0, y: 0.0 })
+            },
+        ));
+        state.add_block_list(BlockList::new(
+            "builtin",
+            "block_1",
+            1,
+            Block {
                 id: "".to_string(),
                 block_type: BlockType::Move,
                 title: "Move".to_string(),
@@ -26,6 +64,8 @@ pub fn Sidebar() -> Element {
                                 title: "Input".to_string(),
                                 value: "input".to_string(),
                                 placeholder: Some("Enter input".to_string()),
+                                value_type: ValueType::Any,
+                                reporter: None,
                             }
                         )
                     }
@@ -35,11 +75,17 @@ pub fn Sidebar() -> Element {
                 parent_id: None,
                 position: Position { x: 0.0, y: 0.0 },
                 z_index: 0,
+                definition: None,
+                body_id: None,
+                output_type: ValueType::Any,
+                target: None,
             },
-        });
-        state.add_block_list(BlockList {
-            name: "block_2".to_string(),
-            block: Block {
+        ));
+        state.add_block_list(BlockList::new(
+            "builtin",
+            "block_2",
+            1,
+            Block {
                 id: "block1".to_string(),
                 block_type: BlockType::Move,
                 title: "Move".to_string(),
@@ -50,21 +96,115 @@ pub fn Sidebar() -> Element {
                 parent_id: None,
                 position: Position { x: 0.0, y: 0.0 },
                 z_index: 1,
+                definition: None,
+                body_id: None,
+                output_type: ValueType::Any,
+                target: None,
             },
-        });
+        ));
+        for block_list in builtin::control_blocks().into_iter().chain(builtin::value_blocks()) {
+            state.add_block_list(block_list);
+        }
+        state.load_user_pack();
+        state.load_palette();
     });
 
+    let state = app_state.read();
+    let all_lists = state.get_all_block_lists();
+    let mut categories: Vec<String> = all_lists.iter().filter_map(|l| l.category.clone()).collect();
+    categories.sort();
+    categories.dedup();
+    let block_types: Vec<BlockType> = BlockType::all()
+        .into_iter()
+        .filter(|t| all_lists.iter().any(|l| &l.block.block_type == t))
+        .collect();
+
+    let mut tabs = vec![(PaletteTab::All, "すべて".to_string())];
+    tabs.extend(block_types.into_iter().map(|t| (PaletteTab::Type(t.clone()), format!("{:?}", t))));
+    tabs.extend(categories.into_iter().map(|c| (PaletteTab::Category(c.clone()), c)));
+    tabs.push((PaletteTab::Favorites, "★".to_string()));
+    tabs.push((PaletteTab::Recent, "最近".to_string()));
+
+    let by_ids = |ids: &[String]| -> Vec<BlockList> {
+        ids.iter().filter_map(|id| state.get_block_list(id)).cloned().collect()
+    };
+    let visible: Vec<BlockList> = match tab() {
+        PaletteTab::All => all_lists.iter().map(|l| (*l).clone()).collect(),
+        PaletteTab::Favorites => by_ids(&state.palette.favorites),
+        PaletteTab::Recent => by_ids(&state.palette.recent),
+        PaletteTab::Type(t) => all_lists.iter().filter(|l| l.block.block_type == t).map(|l| (*l).clone()).collect(),
+        PaletteTab::Category(c) => all_lists
+            .iter()
+            .filter(|l| l.category.as_deref() == Some(c.as_str()))
+            .map(|l| (*l).clone())
+            .collect(),
+    }
+    .into_iter()
+    .filter(|l| l.matches_query(&query()))
+    .collect();
+    let user_pack_id = state.user_pack.id.clone();
+    let favorites = state.palette.favorites.clone();
+    drop(state);
+
     rsx!(
         div {
             id: "sidebar",
 
+            input {
+                class: "sidebar-search",
+                r#type: "search",
+                placeholder: "検索 (タイトル・タグ)",
+                value: "{query}",
+                oninput: move |e: Event<FormData>| query.set(e.value()),
+                onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+            }
+
+            div {
+                class: "sidebar-tabs",
+                for (palette_tab, label) in tabs {
+                    button {
+                        class: if palette_tab == tab() { "sidebar-tab active" } else { "sidebar-tab" },
+                        onclick: move |_| tab.set(palette_tab.clone()),
+                        "{label}"
+                    }
+                }
+            }
+
             ul {
                 style: "list-style-type: none; padding: 0; margin: 0;",
                 
-                for block_list in app_state.read().get_all_block_lists().iter() {
+                for block_list in visible {
                     li {
+                        key: "{block_list.id}",
+                        class: "sidebar-item",
+
+                        button {
+                            class: "sidebar-favorite",
+                            title: "お気に入り",
+                            onclick: {
+                                let id = block_list.id.clone();
+                                move |_| app_state.write().toggle_favorite(&id)
+                            },
+                            if favorites.contains(&block_list.id) { "★" } else { "☆" }
+                        }
+
+                        if block_list.pack() == user_pack_id {
+                            button {
+                                class: "sidebar-edit",
+                                title: "編集",
+                                onclick: {
+                                    let block_list = block_list.clone();
+                                    move |_| {
+                                        editing.set(Some(block_list.clone()));
+                                        show_editor.set(true);
+                                    }
+                                },
+                                "✎"
+                            }
+                        }
+
                         DraggableBlock {
-                            block_data: BlockData::BlockList((*block_list).clone()),
+                            block_data: BlockData::BlockList(block_list.clone()),
                             on_drag_start: move |(id, offset_x, offset_y)| {
                                 app_state.write().dragging = Some((id, offset_x, offset_y));
                             }
@@ -72,6 +212,94 @@ pub fn Sidebar() -> Element {
                     }
                 }
             }
+
+            button {
+                class: "sidebar-button",
+                onclick: move |_| {
+                    editing.set(None);
+                    show_editor.set(true);
+                },
+                "カスタムブロックの作成"
+            }
+
+            button {
+                class: "sidebar-button",
+                onclick: move |_| show_share.set(true),
+                "共有"
+            }
+
+            button {
+                class: "sidebar-button",
+                onclick: move |_| show_import.set(true),
+                "式の読み込み"
+            }
+
+            button {
+                class: "sidebar-button",
+                onclick: move |_| show_comp.set(true),
+                "コンポジション"
+            }
+
+            button {
+                class: "sidebar-button",
+                onclick: move |_| show_variables.set(true),
+                "変数"
+            }
+
+            button {
+                class: "sidebar-button",
+                onclick: move |_| show_functions.set(true),
+                "マイブロック"
+            }
+
+            button {
+                class: "sidebar-button",
+                onclick: move |_| show_settings.set(true),
+                "設定"
+            }
+
+            if show_editor() {
+                BlockEditor {
+                    editing: editing(),
+                    on_close: move |_| show_editor.set(false),
+                }
+            }
+
+            if show_share() {
+                ShareDialog {
+                    on_close: move |_| show_share.set(false),
+                }
+            }
+
+            if show_import() {
+                ImportDialog {
+                    on_close: move |_| show_import.set(false),
+                }
+            }
+
+            if show_comp() {
+                CompDialog {
+                    on_close: move |_| show_comp.set(false),
+                }
+            }
+
+            if show_variables() {
+                VariablesDialog {
+                    on_close: move |_| show_variables.set(false),
+                }
+            }
+
+            if show_functions() {
+                FunctionsDialog {
+                    on_close: move |_| show_functions.set(false),
+                }
+            }
+
+            if show_settings() {
+                SettingsDialog {
+                    on_close: move |_| show_settings.set(false),
+                }
+            }
         }
     )
 }
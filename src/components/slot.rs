@@ -0,0 +1,149 @@
+use dioxus::html::input_data::MouseButton;
+use dioxus::prelude::*;
+
+use crate::models::block::{BlockContent, BlockType, EnumBlockContent};
+use crate::state::app_state::AppState;
+use crate::utils::block::block_colors;
+use crate::utils::types;
+
+// 入力欄か、はめ込まれた値ブロックを表示するフィールド
+#[component]
+pub fn Slot(host_id: String, content: BlockContent, stroke: String, placed: bool, on_change: EventHandler<()>) -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    let EnumBlockContent::ContentValue(cv) = content.content else {
+        return rsx! {};
+    };
+    let key = (host_id.clone(), content.id.clone());
+
+    // 値ブロックをドラッグ中ならはめ込めるかを色で示す
+    let state = app_state.read();
+    let dragged = state
+        .dragging
+        .as_ref()
+        .and_then(|(id, _, _)| state.get_block(id))
+        .filter(|b| b.block_type == BlockType::Value);
+    let class = match dragged {
+        Some(block) if placed && state.hover_slot.as_ref() == Some(&key) => {
+            if cv.value_type.accepts(&types::result_type(&state, block)) {
+                "content-value slot-target"
+            } else {
+                "content-value slot-target mismatch"
+            }
+        }
+        _ => "content-value",
+    };
+    let droppable = placed && dragged.is_some();
+    drop(state);
+
+    rsx! {
+        div {
+            class: class,
+            onmouseenter: {
+                let key = key.clone();
+                move |_| {
+                    if droppable {
+                        app_state.write().hover_slot = Some(key.clone());
+                    }
+                }
+            },
+            onmouseleave: {
+                let key = key.clone();
+                move |_| {
+                    let mut state = app_state.write();
+                    if state.hover_slot.as_ref() == Some(&key) {
+                        state.hover_slot = None;
+                    }
+                }
+            },
+
+            label { "{cv.title}" }
+
+            if let Some(reporter_id) = cv.reporter.clone().filter(|_| placed) {
+                SlotReporter { reporter_id }
+            } else {
+                input {
+                    onmousedown: move |e: MouseEvent| e.stop_propagation(),
+                    onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+                    oninput: {
+                        let (host_id, content_id) = key.clone();
+                        move |e: Event<FormData>| {
+                            e.stop_propagation();
+                            app_state.write().update_block_content(&host_id, &content_id, &e.value());
+                            on_change.call(());
+                        }
+                    },
+                    style: "border: 2px solid {stroke}; border-radius: 9999px; min-width: 60px; width: auto; max-width: calc(100% - 40px); flex: 0 1 auto;",
+                    r#type: "text",
+                    value: "{cv.value}",
+                }
+            }
+
+            if !cv.value_type.badge().is_empty() {
+                span { class: "type-badge", title: "{cv.value_type.label()}", "{cv.value_type.badge()}" }
+            }
+        }
+    }
+}
+
+// スロットの中に描く値ブロック。ドラッグすると取り出せる
+#[component]
+pub fn SlotReporter(reporter_id: String) -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    let Some(block) = app_state.read().get_block(&reporter_id).cloned() else {
+        return rsx! {};
+    };
+    let (fill, stroke) = block_colors(&block.block_type);
+    let bubble = app_state.read().bubble.clone().filter(|b| b.block_id == reporter_id);
+
+    rsx! {
+        div {
+            class: "slot-reporter",
+            style: "background-color: {fill}; border: 2px solid {stroke};",
+            oncontextmenu: {
+                let reporter_id = reporter_id.clone();
+                move |e: MouseEvent| {
+                    e.stop_propagation();
+                    e.prevent_default();
+                    let point = e.client_coordinates();
+                    app_state.write().context_menu = Some((reporter_id.clone(), point.x, point.y));
+                }
+            },
+            onmousedown: {
+                let reporter_id = reporter_id.clone();
+                move |e: MouseEvent| {
+                    e.stop_propagation();
+                    if e.trigger_button() == Some(MouseButton::Secondary) {
+                        return;
+                    }
+                    let page = e.page_coordinates();
+                    let offset = e.element_coordinates();
+                    let mut state = app_state.write();
+                    state.update_block_position(&reporter_id, page.x - offset.x, page.y - offset.y);
+                    state.start_drag(&reporter_id, offset.x, offset.y);
+                }
+            },
+
+            if let Some(bubble) = bubble {
+                match bubble.result {
+                    Some(Ok(text)) => rsx! { div { class: "value-bubble", "{text}" } },
+                    Some(Err(message)) => rsx! { div { class: "value-bubble error", "{message}" } },
+                    None => rsx! { div { class: "value-bubble pending", "…" } },
+                }
+            }
+
+            if block.content.is_empty() {
+                span { class: "slot-reporter-title", "{block.title}" }
+            }
+            for content in block.content.iter().cloned() {
+                Slot {
+                    key: "{content.id}",
+                    host_id: reporter_id.clone(),
+                    content,
+                    stroke: stroke.to_string(),
+                    placed: true,
+                    on_change: move |_| {},
+                }
+            }
+        }
+    }
+}
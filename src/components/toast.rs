@@ -0,0 +1,20 @@
+use dioxus::prelude::*;
+
+use crate::state::app_state::AppState;
+
+#[component]
+pub fn Toast() -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    let toast = app_state.read().toast.clone();
+
+    rsx! {
+        if let Some(toast) = toast {
+            div {
+                key: "{toast.id}",
+                class: "toast",
+                onclick: move |_| app_state.write().toast = None,
+                "{toast.message}"
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use dioxus::prelude::*;
+use uuid::Uuid;
+
+use crate::models::block::ValueType;
+use crate::models::variable::Variable;
+use crate::state::app_state::AppState;
+
+#[component]
+pub fn VariablesDialog(on_close: EventHandler<()>) -> Element {
+    let mut app_state = use_context::<Signal<AppState>>();
+    // 名前を変えても ID は変わらないので、配置済みのブロックも追従する
+    let mut draft = use_signal(|| app_state.read().variables.clone());
+    let mut error = use_signal(|| None::<String>);
+
+    rsx! {
+        div {
+            class: "dialog-backdrop",
+            onmousedown: move |e: MouseEvent| e.stop_propagation(),
+            onkeydown: move |e: KeyboardEvent| e.stop_propagation(),
+
+            div {
+                class: "dialog",
+
+                h2 { "変数" }
+
+                if draft.read().is_empty() {
+                    p { class: "dialog-hint", "変数を追加すると、代入・増減・取得のブロックがパレットの「変数」に並びます" }
+                }
+
+                for (index, variable) in draft().into_iter().enumerate() {
+                    div {
+                        key: "{variable.id}",
+                        class: "dialog-field",
+
+                        input {
+                            r#type: "text",
+                            placeholder: "名前",
+                            value: "{variable.name}",
+                            oninput: move |e: Event<FormData>| draft.write()[index].name = e.value().trim().to_string(),
+                        }
+                        select {
+                            onchange: move |e: Event<FormData>| {
+                                if let Some(t) = ValueType::all().into_iter().find(|t| format!("{:?}", t) == e.value()) {
+                                    draft.write()[index].value_type = t;
+                                }
+                            },
+                            for t in ValueType::all() {
+                                option {
+                                    value: "{t:?}",
+                                    selected: t == variable.value_type,
+                                    "{t.label()}"
+                                }
+                            }
+                        }
+                        input {
+                            r#type: "text",
+                            placeholder: "初期値",
+                            value: "{variable.initial}",
+                            oninput: move |e: Event<FormData>| draft.write()[index].initial = e.value(),
+                        }
+                        button {
+                            onclick: move |_| {
+                                draft.write().remove(index);
+                            },
+                            "削除"
+                        }
+                    }
+                }
+
+                button {
+                    onclick: move |_| {
+                        let mut draft = draft.write();
+                        let name = (1..).map(|n| format!("var{}", n)).find(|name| !draft.iter().any(|v| &v.name == name)).unwrap_or_default();
+                        draft.push(Variable {
+                            id: Uuid::new_v4().simple().to_string(),
+                            name,
+                            value_type: ValueType::Number,
+                            initial: "0".to_string(),
+                        });
+                    },
+                    "+ 変数"
+                }
+
+                if let Some(message) = error() {
+                    p { class: "dialog-error", "{message}" }
+                }
+
+                div {
+                    class: "dialog-actions",
+                    button { onclick: move |_| on_close.call(()), "キャンセル" }
+                    button {
+                        onclick: move |_| match app_state.write().set_variables(draft()) {
+                            Ok(()) => on_close.call(()),
+                            Err(message) => error.set(Some(message)),
+                        },
+                        "保存"
+                    }
+                }
+            }
+        }
+    }
+}
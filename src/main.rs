@@ -4,6 +4,7 @@ use dioxus::prelude::LaunchBuilder;
 use dioxus_desktop::{tao::window::Icon, Config, WindowBuilder};
 
 mod app;
+mod cli;
 pub mod components;
 pub mod models;
 pub mod state;
@@ -12,6 +13,15 @@ pub mod utils;
 const ICON_BYTES: &[u8] = include_bytes!("../icons/favicon.ico");
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("export") {
+        cli::attach_console();
+        return cli::export(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some(utils::sandbox::WORKER_ARG) {
+        return utils::sandbox::run_worker();
+    }
+
     let icon = load_icon_from_bytes(ICON_BYTES).ok();
 
     LaunchBuilder::desktop()
@@ -1,16 +1,19 @@
-#[derive(Clone, Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Position {
     pub x: f64,
     pub y: f64,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Size {
     pub width: f64,
     pub height: f64,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Connection {
     Input,
     Output,
@@ -18,36 +21,250 @@ pub enum Connection {
     None,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum BlockType {
     Flag,
     Works,
     Move,
     Composition,
+    Control,
+    Raw,
+    Value,
+    // マイブロックの定義 (下に本体のスタックをつなぐ)
+    Define,
+}
+
+impl Connection {
+    pub fn all() -> Vec<Connection> {
+        vec![Connection::Input, Connection::Output, Connection::Both, Connection::None]
+    }
+}
+
+impl BlockType {
+    pub fn all() -> Vec<BlockType> {
+        vec![
+            BlockType::Flag,
+            BlockType::Works,
+            BlockType::Move,
+            BlockType::Composition,
+            BlockType::Control,
+            BlockType::Raw,
+            BlockType::Value,
+            BlockType::Define,
+        ]
+    }
+}
+
+// 式が扱う値の型 (Any は検査しない)
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ValueType {
+    #[default]
+    Any,
+    Number,
+    Array2,
+    Array3,
+    Array4,
+    String,
+    Boolean,
+    Layer,
+    Property,
+}
+
+impl ValueType {
+    pub fn all() -> Vec<ValueType> {
+        vec![
+            ValueType::Any,
+            ValueType::Number,
+            ValueType::Array2,
+            ValueType::Array3,
+            ValueType::Array4,
+            ValueType::String,
+            ValueType::Boolean,
+            ValueType::Layer,
+            ValueType::Property,
+        ]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ValueType::Any => "任意",
+            ValueType::Number => "数値",
+            ValueType::Array2 => "配列[2]",
+            ValueType::Array3 => "配列[3]",
+            ValueType::Array4 => "配列[4]",
+            ValueType::String => "文字列",
+            ValueType::Boolean => "真偽値",
+            ValueType::Layer => "レイヤー",
+            ValueType::Property => "プロパティ",
+        }
+    }
+
+    // スロットに表示する短い印
+    pub fn badge(&self) -> &'static str {
+        match self {
+            ValueType::Any => "",
+            ValueType::Number => "#",
+            ValueType::Array2 => "[2]",
+            ValueType::Array3 => "[3]",
+            ValueType::Array4 => "[4]",
+            ValueType::String => "abc",
+            ValueType::Boolean => "?",
+            ValueType::Layer => "L",
+            ValueType::Property => "P",
+        }
+    }
+
+    // 新しく置いたブロックの入力欄に入れておく値
+    pub fn default_value(&self) -> &'static str {
+        match self {
+            ValueType::Any | ValueType::Number => "0",
+            ValueType::Array2 => "[0, 0]",
+            ValueType::Array3 => "[0, 0, 0]",
+            ValueType::Array4 => "[0, 0, 0, 0]",
+            ValueType::String => "\"\"",
+            ValueType::Boolean => "false",
+            ValueType::Layer => "thisLayer",
+            ValueType::Property => "thisProperty",
+        }
+    }
+
+    pub fn from_dimension(dimension: usize) -> ValueType {
+        match dimension {
+            1 => ValueType::Number,
+            2 => ValueType::Array2,
+            3 => ValueType::Array3,
+            4 => ValueType::Array4,
+            _ => ValueType::Any,
+        }
+    }
+
+    pub fn accepts(&self, other: &ValueType) -> bool {
+        *self == ValueType::Any || *other == ValueType::Any || self == other
+    }
+}
+
+// Flag の式を貼り付けるプロパティ
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TargetProperty {
+    Position,
+    Scale,
+    Rotation,
+    Opacity,
+    SourceText,
+    Path,
+    Color,
+    Custom { dimension: usize },
+}
+
+impl TargetProperty {
+    pub fn all() -> Vec<TargetProperty> {
+        vec![
+            TargetProperty::Position,
+            TargetProperty::Scale,
+            TargetProperty::Rotation,
+            TargetProperty::Opacity,
+            TargetProperty::SourceText,
+            TargetProperty::Path,
+            TargetProperty::Color,
+            TargetProperty::Custom { dimension: 1 },
+        ]
+    }
+
+    // 選択肢の値 (カスタムの次元は含めない)
+    pub fn key(&self) -> &'static str {
+        match self {
+            TargetProperty::Position => "position",
+            TargetProperty::Scale => "scale",
+            TargetProperty::Rotation => "rotation",
+            TargetProperty::Opacity => "opacity",
+            TargetProperty::SourceText => "sourceText",
+            TargetProperty::Path => "path",
+            TargetProperty::Color => "color",
+            TargetProperty::Custom { .. } => "custom",
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            TargetProperty::Position => "位置".to_string(),
+            TargetProperty::Scale => "スケール".to_string(),
+            TargetProperty::Rotation => "回転".to_string(),
+            TargetProperty::Opacity => "不透明度".to_string(),
+            TargetProperty::SourceText => "ソーステキスト".to_string(),
+            TargetProperty::Path => "パス".to_string(),
+            TargetProperty::Color => "カラー".to_string(),
+            TargetProperty::Custom { dimension } => format!("カスタム ({} 次元)", dimension),
+        }
+    }
+
+    // AE のタイムラインでの場所
+    pub fn location(&self) -> String {
+        match self {
+            TargetProperty::Position
+            | TargetProperty::Scale
+            | TargetProperty::Rotation
+            | TargetProperty::Opacity => format!("トランスフォーム > {}", self.label()),
+            TargetProperty::SourceText => "テキスト > ソーステキスト".to_string(),
+            TargetProperty::Path => "コンテンツ > シェイプ > パス".to_string(),
+            TargetProperty::Color => "エフェクト > カラー".to_string(),
+            TargetProperty::Custom { dimension } => format!("{} 次元のプロパティ", dimension),
+        }
+    }
+
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            TargetProperty::Position | TargetProperty::Scale => ValueType::Array2,
+            TargetProperty::Rotation | TargetProperty::Opacity => ValueType::Number,
+            TargetProperty::SourceText => ValueType::String,
+            TargetProperty::Path => ValueType::Any,
+            TargetProperty::Color => ValueType::Array4,
+            TargetProperty::Custom { dimension } => ValueType::from_dimension(*dimension),
+        }
+    }
+
+    // プレビューで value として使う既定値
+    pub fn default_value(&self) -> serde_json::Value {
+        match self {
+            TargetProperty::Position => json!([960, 540]),
+            TargetProperty::Scale => json!([100, 100]),
+            TargetProperty::Rotation => json!(0),
+            TargetProperty::Opacity => json!(100),
+            TargetProperty::SourceText => json!("Text"),
+            TargetProperty::Path => serde_json::Value::Null,
+            TargetProperty::Color => json!([1, 1, 1, 1]),
+            TargetProperty::Custom { dimension: 1 } => json!(0),
+            TargetProperty::Custom { dimension } => json!(vec![0; *dimension]),
+        }
+    }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ContentValue {
     pub title: String,
     pub value: String,
     pub placeholder: Option<String>,
+    #[serde(default)]
+    pub value_type: ValueType,
+    // はめ込まれた値ブロック (あれば value より優先する)
+    #[serde(default)]
+    pub reporter: Option<String>,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Separator {
     None,
     Space,
     Newline,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Options {
     pub id: String,
     pub title: String,
     pub value: String,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ContentSelector {
     pub title: String,
     pub value: String,
@@ -55,20 +272,26 @@ pub struct ContentSelector {
     pub placeholder: Option<String>,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum EnumBlockContent {
     ContentValue(ContentValue),
     ContentSelector(ContentSelector),
     Separator(Separator),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct BlockContent {
     pub id: String,
     pub content: EnumBlockContent,
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DefinitionRef {
+    pub id: String,
+    pub version: u32,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Block {
     pub id: String,
     pub block_type: BlockType,
@@ -80,6 +303,17 @@ pub struct Block {
     pub parent_id: Option<String>,
     pub position: Position,
     pub z_index: i32,
+    #[serde(default)]
+    pub definition: Option<DefinitionRef>,
+    // C ブロックの内側のスタックの先頭
+    #[serde(default)]
+    pub body_id: Option<String>,
+    // 値ブロックが返す値の型
+    #[serde(default)]
+    pub output_type: ValueType,
+    // Flag の貼り付け先
+    #[serde(default)]
+    pub target: Option<TargetProperty>,
 }
 
 impl Block {
@@ -100,6 +334,67 @@ impl Block {
             parent_id: None,
             position,
             z_index: 0,
+            definition: None,
+            body_id: None,
+            output_type: ValueType::Any,
+            target: None,
         }
     }
+
+    pub fn has_body(&self) -> bool {
+        self.block_type == BlockType::Control
+    }
+
+    // 定義の内容に置き換え、入力済みの値は BlockContent.id で引き継ぐ
+    pub fn apply_definition(&mut self, template: &Block, definition: DefinitionRef) {
+        let content = template
+            .content
+            .iter()
+            .map(|new_content| {
+                let mut new_content = new_content.clone();
+                let old = self.content.iter().find(|c| c.id == new_content.id);
+                match (&mut new_content.content, old.map(|c| &c.content)) {
+                    (EnumBlockContent::ContentValue(cv), Some(EnumBlockContent::ContentValue(old))) => {
+                        cv.value = old.value.clone();
+                        cv.reporter = old.reporter.clone();
+                    }
+                    (EnumBlockContent::ContentSelector(cs), Some(EnumBlockContent::ContentSelector(old)))
+                        if cs.options.iter().any(|o| o.value == old.value) =>
+                    {
+                        cs.value = old.value.clone();
+                    }
+                    _ => {}
+                }
+                new_content
+            })
+            .collect();
+
+        self.block_type = template.block_type.clone();
+        self.title = template.title.clone();
+        self.output = template.output.clone();
+        self.connection = template.connection.clone();
+        self.output_type = template.output_type.clone();
+        self.content = content;
+        self.definition = Some(definition);
+    }
+
+    pub fn reporter_slots(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.content.iter().filter_map(|c| match &c.content {
+            EnumBlockContent::ContentValue(ContentValue { reporter: Some(reporter), .. }) => {
+                Some((c.id.as_str(), reporter.as_str()))
+            }
+            _ => None,
+        })
+    }
+
+    pub fn content_value(&self, content_id: &str) -> Option<String> {
+        self.content
+            .iter()
+            .find(|c| c.id == content_id)
+            .and_then(|c| match &c.content {
+                EnumBlockContent::ContentValue(cv) => Some(cv.value.clone()),
+                EnumBlockContent::ContentSelector(cs) => Some(cs.value.clone()),
+                EnumBlockContent::Separator(_) => None,
+            })
+    }
 }
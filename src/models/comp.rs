@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PropertyValue {
+    Scalar(f64),
+    Vector(Vec<f64>),
+}
+
+impl PropertyValue {
+    // "12.5" や "[960, 540]" の形式
+    pub fn parse(text: &str) -> Option<Self> {
+        serde_json::from_str(text.trim()).ok()
+    }
+
+    pub fn to_text(&self) -> String {
+        match self {
+            PropertyValue::Scalar(v) => v.to_string(),
+            PropertyValue::Vector(values) => format!(
+                "[{}]",
+                values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: f64,
+    pub value: PropertyValue,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MockProperty {
+    pub name: String,
+    pub value: PropertyValue,
+    #[serde(default)]
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl MockProperty {
+    pub fn new(name: &str, value: PropertyValue) -> Self {
+        Self {
+            name: name.to_string(),
+            value,
+            keyframes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MockEffect {
+    pub name: String,
+    #[serde(default)]
+    pub params: Vec<MockProperty>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MockLayer {
+    pub name: String,
+    #[serde(default)]
+    pub properties: Vec<MockProperty>,
+    #[serde(default)]
+    pub effects: Vec<MockEffect>,
+}
+
+impl MockLayer {
+    // トランスフォームのプロパティを既定値で持つレイヤー
+    pub fn new(name: &str, width: f64, height: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            properties: vec![
+                MockProperty::new("anchorPoint", PropertyValue::Vector(vec![0.0, 0.0])),
+                MockProperty::new("position", PropertyValue::Vector(vec![width / 2.0, height / 2.0])),
+                MockProperty::new("scale", PropertyValue::Vector(vec![100.0, 100.0])),
+                MockProperty::new("rotation", PropertyValue::Scalar(0.0)),
+                MockProperty::new("opacity", PropertyValue::Scalar(100.0)),
+            ],
+            effects: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MockComp {
+    pub name: String,
+    pub frame_rate: f64,
+    pub duration: f64,
+    pub width: f64,
+    pub height: f64,
+    #[serde(default)]
+    pub layers: Vec<MockLayer>,
+}
+
+impl Default for MockComp {
+    fn default() -> Self {
+        Self {
+            name: "Comp 1".to_string(),
+            frame_rate: 30.0,
+            duration: 10.0,
+            width: 1920.0,
+            height: 1080.0,
+            layers: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::block::ValueType;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Parameter {
+    // 取得ブロックと呼び出しのフィールドの ID に使うので、並べ替えや名前の変更では変えない
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub value_type: ValueType,
+}
+
+// マイブロック。本体は定義ブロックの下につないだスタック
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Function {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub params: Vec<Parameter>,
+}
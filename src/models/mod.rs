@@ -1 +1,4 @@
 pub mod block;
+pub mod comp;
+pub mod function;
+pub mod variable;
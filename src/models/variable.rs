@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::block::ValueType;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Variable {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub value_type: ValueType,
+    // var 宣言の初期値 (式のまま)
+    pub initial: String,
+}
@@ -1,52 +1,221 @@
-use std::collections::HashMap;
-use crate::models::block::{Block, BlockContent};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use crate::models::block::{Block, BlockContent, BlockType, DefinitionRef, Position};
+use crate::models::comp::MockComp;
+use crate::models::function::Function;
+use crate::models::variable::Variable;
+use crate::state::library::{LibraryPack, PaletteHistory};
+use crate::state::output::{PreviewContext, Toast, ValueBubble};
+use crate::state::settings::Settings;
+use crate::state::project::{Project, PROJECT_FORMAT_VERSION};
+use crate::utils::block::Size;
+use crate::utils::parser;
+use crate::utils::share::SharePayload;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
 use uuid::Uuid;
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct BlockList {
+    // "pack:name" 形式
+    #[serde(default)]
+    pub id: String,
     pub name: String,
+    #[serde(default = "default_version")]
+    pub version: u32,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
     pub block: Block,
 }
 
+fn default_version() -> u32 {
+    1
+}
+
+impl BlockList {
+    pub fn new(pack: &str, name: &str, version: u32, block: Block) -> Self {
+        Self {
+            id: format!("{}:{}", pack, name),
+            name: name.to_string(),
+            version,
+            category: None,
+            tags: Vec::new(),
+            block,
+        }
+    }
+
+    pub fn matches_query(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        query.is_empty()
+            || self.block.title.to_lowercase().contains(&query)
+            || self.tags.iter().any(|tag| tag.to_lowercase().contains(&query))
+    }
+
+    pub fn pack(&self) -> &str {
+        self.id.split_once(':').map(|(pack, _)| pack).unwrap_or_default()
+    }
+
+    pub fn definition_ref(&self) -> DefinitionRef {
+        DefinitionRef {
+            id: self.id.clone(),
+            version: self.version,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DefinitionStatus {
+    Resolved,
+    Missing,
+    Newer { library_version: u32 },
+}
+
 #[derive(Default, Clone)]
 pub struct AppState {
     pub block_list: HashMap<String, BlockList>,
     pub blocks: HashMap<String, Block>,
     pub dragging: Option<(String, f64, f64)>,
+    pub drag_origin: Option<Position>,
+    pub user_pack: LibraryPack,
+    pub palette: PaletteHistory,
+    pub selected: Option<String>,
+    pub block_sizes: HashMap<String, Size>,
+    pub settings: Settings,
+    pub active_output: Option<String>,
+    pub highlighted_block: Option<String>,
+    // デバッガで実行中のブロック
+    pub debug_block: Option<String>,
+    pub breakpoints: HashSet<String>,
+    pub toast: Option<Toast>,
+    pub comp: MockComp,
+    // Output のプレビューと値ブロックのクリックで共有する評価の条件
+    pub preview: PreviewContext,
+    pub bubble: Option<ValueBubble>,
+    // 右クリックしたブロックとメニューの表示位置
+    pub context_menu: Option<(String, f64, f64)>,
+    // 値ブロックをドラッグ中にポインタが乗っているスロット (ホスト, フィールド ID)
+    pub hover_slot: Option<(String, String)>,
+    // Flag ごとの評価が上限で中断されたときのメッセージ
+    pub evaluation_errors: HashMap<String, String>,
+    pub variables: Vec<Variable>,
+    pub functions: Vec<Function>,
+    // Flag からたどれるブロックと、そこから呼ばれるマイブロックの本体
+    pub reachable: HashSet<String>,
+    pub function_reachable: HashSet<String>,
+    // ポインタを乗せている Flag と、強調するそのスタック
+    pub hovered_flag: Option<String>,
+    pub hovered_stack: HashSet<String>,
 }
 
 impl AppState {
     pub fn add_block_list(&mut self, block: BlockList) {
         self.block_list.insert(
-            block.name.clone(),
+            block.id.clone(),
             block.clone()
         );
+        self.sync_instances(&block.id);
     }
 
-    pub fn remove_block_list(&mut self, name: &str) {
-        self.block_list.remove(name);
+    pub fn sync_instances(&mut self, list_id: &str) {
+        let Some(template) = self.block_list.get(list_id) else {
+            return;
+        };
+        for block in self.blocks.values_mut() {
+            let linked = block
+                .definition
+                .as_ref()
+                .is_some_and(|d| d.id == template.id && d.version <= template.version);
+            if linked {
+                block.apply_definition(&template.block, template.definition_ref());
+            }
+        }
+        // 定義からフィールドが消えたら、はめ込まれていた値ブロックを外す
+        let released: Vec<String> = self
+            .blocks
+            .values()
+            .filter(|b| b.block_type == BlockType::Value && b.parent_id.is_some())
+            .filter(|b| !self.is_plugged(&b.id))
+            .map(|b| b.id.clone())
+            .collect();
+        for id in released {
+            self.unmark_reachable(&id);
+            if let Some(block) = self.blocks.get_mut(&id) {
+                block.parent_id = None;
+            }
+        }
+    }
+
+    pub fn remove_block_list(&mut self, id: &str) {
+        self.block_list.remove(id);
     }
 
     pub fn clear_block_list(&mut self) {
         self.block_list.clear();
     }
 
-    pub fn get_block_list(&self, name: &str) -> Option<&BlockList> {
-        self.block_list.get(name)
+    pub fn get_block_list(&self, id: &str) -> Option<&BlockList> {
+        self.block_list.get(id)
     }
 
     pub fn get_all_block_lists(&self) -> Vec<&BlockList> {
-        self.block_list.values().collect()
+        let type_order = |t: &BlockType| BlockType::all().iter().position(|x| x == t);
+        let mut lists: Vec<&BlockList> = self.block_list.values().collect();
+        lists.sort_by(|a, b| {
+            type_order(&a.block.block_type)
+                .cmp(&type_order(&b.block.block_type))
+                .then_with(|| a.block.title.cmp(&b.block.title))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        lists
     }
 
-    pub fn add_block(&mut self, name: &str, x: f64, y: f64) -> Option<String> {
-        if let Some(template) = self.block_list.get(name) {
+    pub fn load_settings(&mut self) {
+        self.settings = Settings::load();
+    }
+
+    pub fn save_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+        if let Err(e) = self.settings.save() {
+            warn!("Failed to save settings: {}", e);
+        }
+    }
+
+    pub fn load_palette(&mut self) {
+        self.palette = PaletteHistory::load();
+    }
+
+    pub fn toggle_favorite(&mut self, list_id: &str) {
+        self.palette.toggle_favorite(list_id);
+        if let Err(e) = self.palette.save() {
+            warn!("Failed to save palette history: {}", e);
+        }
+    }
+
+    pub fn load_user_pack(&mut self) {
+        self.user_pack = LibraryPack::load_user();
+        for block in self.user_pack.blocks.clone() {
+            self.add_block_list(block);
+        }
+    }
+
+    pub fn save_custom_block(&mut self, block: BlockList) -> io::Result<()> {
+        self.user_pack.upsert(block.clone());
+        self.add_block_list(block);
+        self.user_pack.save_user()
+    }
+
+    pub fn add_block(&mut self, list_id: &str, x: f64, y: f64) -> Option<String> {
+        if let Some(template) = self.block_list.get(list_id) {
             let id = Uuid::new_v4().to_string();
             let mut new_block = template.block.clone();
             new_block.id = id.clone();
+            new_block.definition = Some(template.definition_ref());
             new_block.position.x = x;
             new_block.position.y = y;
             self.blocks.insert(id.clone(), new_block);
+            self.mark_reachable(&id);
             Some(id)
         } else {
             None
@@ -76,10 +245,15 @@ impl AppState {
 
     pub fn remove_block(&mut self, id: &str) {
         self.blocks.remove(id);
+        self.reachable.remove(id);
+        self.function_reachable.remove(id);
+        self.breakpoints.remove(id);
     }
 
     pub fn clear_blocks(&mut self) {
         self.blocks.clear();
+        self.breakpoints.clear();
+        self.refresh_reachable();
     }
 
     pub fn get_block(&self, id: &str) -> Option<&Block> {
@@ -89,4 +263,201 @@ impl AppState {
     pub fn get_all_blocks(&self) -> Vec<&Block> {
         self.blocks.values().collect()
     }
+
+    pub fn definition_status(&self, block: &Block) -> DefinitionStatus {
+        let Some(definition) = &block.definition else {
+            return DefinitionStatus::Resolved;
+        };
+        match self.block_list.get(&definition.id) {
+            None => DefinitionStatus::Missing,
+            Some(list) if list.version < definition.version => DefinitionStatus::Newer {
+                library_version: list.version,
+            },
+            Some(_) => DefinitionStatus::Resolved,
+        }
+    }
+
+    pub fn to_project(&self) -> Project {
+        Project {
+            format_version: PROJECT_FORMAT_VERSION,
+            blocks: self.blocks.values().cloned().collect(),
+            comp: self.comp.clone(),
+            variables: self.variables.clone(),
+            functions: self.functions.clone(),
+        }
+    }
+
+    // 解決できない定義を参照するブロックもデータはそのまま読み込み、未解決の定義を返す
+    pub fn load_project(&mut self, project: Project) -> Vec<DefinitionRef> {
+        self.blocks = project
+            .blocks
+            .into_iter()
+            .map(|block| (block.id.clone(), block))
+            .collect();
+        self.comp = project.comp;
+        self.breakpoints.clear();
+        self.register_variables(project.variables);
+        self.register_functions(project.functions);
+        self.dragging = None;
+
+        let list_ids: Vec<String> = self.block_list.keys().cloned().collect();
+        for list_id in list_ids {
+            self.sync_instances(&list_id);
+        }
+        self.refresh_reachable();
+
+        self.unresolved_definitions(self.blocks.values())
+    }
+
+    pub fn unresolved_definitions<'a>(&self, blocks: impl Iterator<Item = &'a Block>) -> Vec<DefinitionRef> {
+        let mut unresolved: Vec<DefinitionRef> = Vec::new();
+        for block in blocks {
+            if self.definition_status(block) == DefinitionStatus::Resolved {
+                continue;
+            }
+            if let Some(definition) = &block.definition {
+                if !unresolved.contains(definition) {
+                    unresolved.push(definition.clone());
+                }
+            }
+        }
+        unresolved
+    }
+
+    // プロジェクトの共有コードは現在のワークスペースを置き換える
+    pub fn import_share(&mut self, payload: SharePayload, at: (f64, f64)) -> Vec<DefinitionRef> {
+        match payload {
+            SharePayload::Project(project) => self.load_project(project),
+            SharePayload::Stack(blocks) => self.paste_blocks(blocks, at),
+        }
+    }
+
+    // コピーしたあとに変わった定義に合わせて追加し、未解決の定義を返す
+    pub fn paste_blocks(&mut self, blocks: Vec<Block>, at: (f64, f64)) -> Vec<DefinitionRef> {
+        let roots = self.insert_blocks(blocks, Some(at));
+        let ids: Vec<String> = roots.iter().flat_map(|root| self.tree_ids(root)).collect();
+        for id in &ids {
+            let list_id = self.blocks.get(id).and_then(|b| b.definition.as_ref()).map(|d| d.id.clone());
+            if let Some(list_id) = list_id {
+                self.sync_instances(&list_id);
+            }
+        }
+        self.selected = roots.first().cloned();
+        self.unresolved_definitions(ids.iter().filter_map(|id| self.blocks.get(id)))
+    }
+
+    // 式を解析して空いている場所に配置し、追加したブロックの ID を返す
+    pub fn import_expression(&mut self, source: &str) -> Vec<String> {
+        let blocks = parser::parse(self, source);
+        let at = self.free_position();
+        let roots = self.insert_blocks(blocks, Some(at));
+        self.selected = roots.first().cloned();
+        roots.iter().flat_map(|root| self.tree_ids(root)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::block::{Connection, ContentValue, EnumBlockContent};
+
+    fn field(id: &str) -> BlockContent {
+        BlockContent {
+            id: id.to_string(),
+            content: EnumBlockContent::ContentValue(ContentValue {
+                title: id.to_string(),
+                value: String::new(),
+                placeholder: None,
+                value_type: Default::default(),
+                reporter: None,
+            }),
+        }
+    }
+
+    fn works(title: &str, fields: &[&str]) -> Block {
+        Block {
+            connection: Connection::Both,
+            output: format!("{}();", title),
+            content: fields.iter().map(|id| field(id)).collect(),
+            ..Block::new(String::new(), BlockType::Works, title.to_string(), Position { x: 0.0, y: 0.0 })
+        }
+    }
+
+    fn state() -> AppState {
+        let mut state = AppState::default();
+        state.add_block_list(BlockList::new("user", "wiggle", 1, works("wiggle", &["f", "a"])));
+        let value = Block {
+            connection: Connection::Output,
+            output: "1".to_string(),
+            ..Block::new(String::new(), BlockType::Value, "one".to_string(), Position { x: 0.0, y: 0.0 })
+        };
+        state.add_block_list(BlockList::new("user", "one", 1, value));
+        state
+    }
+
+    #[test]
+    fn new_definition_keeps_values_by_content_id() {
+        let mut state = state();
+        let id = state.add_block("user:wiggle", 0.0, 0.0).unwrap();
+        state.update_block_content(&id, "f", "2");
+        state.update_block_content(&id, "a", "30");
+
+        // "a" の位置を入れ替え、"s" を足して版を上げる
+        state.add_block_list(BlockList::new("user", "wiggle", 2, works("wiggle2", &["s", "a", "f"])));
+
+        let block = state.get_block(&id).unwrap();
+        assert_eq!(block.title, "wiggle2");
+        assert_eq!(block.definition.as_ref().map(|d| d.version), Some(2));
+        assert_eq!(block.content_value("f").as_deref(), Some("2"));
+        assert_eq!(block.content_value("a").as_deref(), Some("30"));
+        assert_eq!(block.content_value("s").as_deref(), Some(""));
+        assert_eq!(state.definition_status(block), DefinitionStatus::Resolved);
+    }
+
+    #[test]
+    fn removed_content_releases_plugged_reporter() {
+        let mut state = state();
+        let host = state.add_block("user:wiggle", 0.0, 0.0).unwrap();
+        let reporter = state.add_block("user:one", 0.0, 0.0).unwrap();
+        state.plug(&host, "a", &reporter).unwrap();
+
+        state.add_block_list(BlockList::new("user", "wiggle", 2, works("wiggle", &["f"])));
+
+        let block = state.get_block(&host).unwrap();
+        assert!(block.content.iter().all(|c| c.id != "a"));
+        assert_eq!(block.reporter_slots().count(), 0);
+        assert_eq!(state.get_block(&reporter).unwrap().parent_id, None);
+        assert!(!state.is_plugged(&reporter));
+    }
+
+    #[test]
+    fn missing_and_newer_definitions_are_left_untouched() {
+        let mut state = state();
+        let missing = state.add_block("user:wiggle", 0.0, 0.0).unwrap();
+        let newer = state.add_block("user:wiggle", 0.0, 0.0).unwrap();
+        state.update_block_content(&newer, "f", "5");
+        state.blocks.get_mut(&missing).unwrap().definition = Some(DefinitionRef {
+            id: "user:gone".to_string(),
+            version: 1,
+        });
+        state.blocks.get_mut(&newer).unwrap().definition = Some(DefinitionRef {
+            id: "user:wiggle".to_string(),
+            version: 3,
+        });
+
+        state.add_block_list(BlockList::new("user", "wiggle", 2, works("wiggle2", &["s"])));
+
+        let block = state.get_block(&missing).unwrap();
+        assert_eq!(block.title, "wiggle");
+        assert_eq!(state.definition_status(block), DefinitionStatus::Missing);
+        let block = state.get_block(&newer).unwrap();
+        assert_eq!(block.title, "wiggle");
+        assert_eq!(block.content_value("f").as_deref(), Some("5"));
+        assert_eq!(state.definition_status(block), DefinitionStatus::Newer { library_version: 2 });
+
+        let unresolved = state.unresolved_definitions(state.blocks.values());
+        assert_eq!(unresolved.len(), 2);
+        assert!(unresolved.iter().any(|d| d.id == "user:gone"));
+        assert!(unresolved.iter().any(|d| d.id == "user:wiggle" && d.version == 3));
+    }
 }
@@ -0,0 +1,155 @@
+use crate::models::block::{Block, BlockContent, BlockType, Connection, ContentValue, EnumBlockContent, Position, ValueType};
+use crate::state::app_state::BlockList;
+
+pub const FLAG: &str = "builtin:flag";
+pub const IF: &str = "builtin:if";
+pub const ELSE: &str = "builtin:else";
+pub const FOR: &str = "builtin:for";
+pub const WHILE: &str = "builtin:while";
+pub const RAW: &str = "builtin:raw";
+
+fn value(id: &str, title: &str, value: &str) -> BlockContent {
+    typed(id, title, value, ValueType::Any)
+}
+
+fn typed(id: &str, title: &str, value: &str, value_type: ValueType) -> BlockContent {
+    BlockContent {
+        id: id.to_string(),
+        content: EnumBlockContent::ContentValue(ContentValue {
+            title: title.to_string(),
+            value: value.to_string(),
+            placeholder: None,
+            value_type,
+            reporter: None,
+        }),
+    }
+}
+
+fn definition(name: &str, block_type: BlockType, title: &str, output: &str, content: Vec<BlockContent>) -> BlockList {
+    let mut list = BlockList::new(
+        "builtin",
+        name,
+        1,
+        Block {
+            output: output.to_string(),
+            content,
+            connection: Connection::Both,
+            ..Block::new(String::new(), block_type, title.to_string(), Position { x: 0.0, y: 0.0 })
+        },
+    );
+    list.category = Some("制御".to_string());
+    list
+}
+
+fn reporter(name: &str, title: &str, output: &str, output_type: ValueType, content: Vec<BlockContent>) -> BlockList {
+    let mut list = BlockList::new(
+        "builtin",
+        name,
+        1,
+        Block {
+            output: output.to_string(),
+            content,
+            connection: Connection::None,
+            output_type,
+            ..Block::new(String::new(), BlockType::Value, title.to_string(), Position { x: 0.0, y: 0.0 })
+        },
+    );
+    list.category = Some("値".to_string());
+    list
+}
+
+pub fn value_blocks() -> Vec<BlockList> {
+    vec![
+        reporter("time", "時間", "time", ValueType::Number, vec![]),
+        reporter("value", "元の値", "value", ValueType::Any, vec![]),
+        reporter(
+            "wiggle",
+            "ウィグル",
+            "wiggle({{freq}}, {{amp}})",
+            ValueType::Any,
+            vec![
+                typed("freq", "周波数", "2", ValueType::Number),
+                typed("amp", "振幅", "50", ValueType::Number),
+            ],
+        ),
+        reporter(
+            "linear",
+            "リニア",
+            "linear({{t}}, {{from}}, {{to}})",
+            ValueType::Any,
+            vec![
+                typed("t", "時間", "time", ValueType::Number),
+                value("from", "開始", "0"),
+                value("to", "終了", "100"),
+            ],
+        ),
+        reporter(
+            "vec2",
+            "2D",
+            "[{{x}}, {{y}}]",
+            ValueType::Array2,
+            vec![typed("x", "x", "0", ValueType::Number), typed("y", "y", "0", ValueType::Number)],
+        ),
+        reporter(
+            "vec3",
+            "3D",
+            "[{{x}}, {{y}}, {{z}}]",
+            ValueType::Array3,
+            vec![
+                typed("x", "x", "0", ValueType::Number),
+                typed("y", "y", "0", ValueType::Number),
+                typed("z", "z", "0", ValueType::Number),
+            ],
+        ),
+        reporter(
+            "layer",
+            "レイヤー",
+            "thisComp.layer({{name}})",
+            ValueType::Layer,
+            vec![typed("name", "名前", "\"Layer 1\"", ValueType::String)],
+        ),
+        reporter(
+            "position",
+            "位置",
+            "{{layer}}.transform.position",
+            ValueType::Property,
+            vec![typed("layer", "レイヤー", "thisLayer", ValueType::Layer)],
+        ),
+    ]
+}
+
+// 式の読み込みでも使うので、パレットに登録されていなくても参照できるようにする
+pub fn control_blocks() -> Vec<BlockList> {
+    vec![
+        definition(
+            "if",
+            BlockType::Control,
+            "もし",
+            "if ({{condition}}) {\n{{body}}\n}",
+            vec![typed("condition", "もし", "true", ValueType::Boolean)],
+        ),
+        definition("else", BlockType::Control, "でなければ", "else {\n{{body}}\n}", vec![]),
+        definition(
+            "for",
+            BlockType::Control,
+            "繰り返し",
+            "for ({{header}}) {\n{{body}}\n}",
+            vec![value("header", "繰り返し", "let i = 0; i < 10; i++")],
+        ),
+        definition(
+            "while",
+            BlockType::Control,
+            "の間",
+            "while ({{condition}}) {\n{{body}}\n}",
+            vec![typed("condition", "の間", "true", ValueType::Boolean)],
+        ),
+        definition(
+            "return",
+            BlockType::Works,
+            "返す",
+            "return {{value}};",
+            vec![value("value", "返す", "0")],
+        ),
+        definition("raw", BlockType::Raw, "コード", "{{code}}", vec![value("code", "コード", "")]),
+    ]
+}
@@ -0,0 +1,199 @@
+use uuid::Uuid;
+
+use crate::models::block::{Block, BlockContent, BlockType, Connection, ContentValue, EnumBlockContent, Position, ValueType};
+use crate::state::app_state::{AppState, BlockList};
+use crate::utils::{generator, types};
+
+// 切り出したブロックの引数になるフィールド
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExtractParam {
+    pub block_id: String,
+    pub content_id: String,
+    pub title: String,
+    pub value: String,
+    pub value_type: ValueType,
+    pub reporter: Option<String>,
+}
+
+enum Anchor {
+    Next(String),
+    Body(String),
+    Slot(String, String),
+    Free,
+}
+
+impl AppState {
+    // 値ブロックがはめ込まれているか、定義の既定値から書き換えたフィールドを引数の候補にする
+    pub fn extract_candidates(&self, root: &str) -> Result<Vec<ExtractParam>, String> {
+        let block = self.get_block(root).ok_or_else(|| "ブロックが見つかりません".to_string())?;
+        if matches!(block.block_type, BlockType::Flag | BlockType::Define) {
+            return Err(format!("\"{}\" はブロックに切り出せません", block.title));
+        }
+        let mut params = Vec::new();
+        for id in self.tree_ids(root) {
+            if id != root && self.is_plugged(&id) {
+                continue;
+            }
+            let Some(block) = self.get_block(&id) else {
+                continue;
+            };
+            // 変数やマイブロックは宣言が別に出力されるので、そのままでは切り出せない
+            if self.variable_of(block).is_some() || self.function_of(block).is_some() {
+                return Err(format!("\"{}\" を含むスタックはブロックに切り出せません", block.title));
+            }
+            let defaults = block
+                .definition
+                .as_ref()
+                .and_then(|d| self.get_block_list(&d.id))
+                .map(|list| &list.block);
+            for content in &block.content {
+                let EnumBlockContent::ContentValue(cv) = &content.content else {
+                    continue;
+                };
+                let default = defaults.and_then(|d| d.content_value(&content.id));
+                if cv.reporter.is_none() && default.as_deref() == Some(cv.value.as_str()) {
+                    continue;
+                }
+                params.push(ExtractParam {
+                    block_id: block.id.clone(),
+                    content_id: content.id.clone(),
+                    title: cv.title.clone(),
+                    value: cv.value.clone(),
+                    value_type: cv.value_type.clone(),
+                    reporter: cv.reporter.clone(),
+                });
+            }
+        }
+        Ok(params)
+    }
+
+    // 選んだフィールドを引数にした出力テンプレートを作る。切り出す部分だけを写した状態で組み立てる
+    pub fn extract_template(&self, root: &str, params: &[ExtractParam]) -> String {
+        let mut scratch = AppState {
+            blocks: self
+                .tree_ids(root)
+                .into_iter()
+                .filter_map(|id| self.blocks.get(&id).map(|block| (id, block.clone())))
+                .collect(),
+            ..Default::default()
+        };
+        for (index, param) in params.iter().enumerate() {
+            let Some(block) = scratch.blocks.get_mut(&param.block_id) else {
+                continue;
+            };
+            let content = block.content.iter_mut().find(|c| c.id == param.content_id);
+            if let Some(EnumBlockContent::ContentValue(cv)) = content.map(|c| &mut c.content) {
+                cv.value = format!("{{{{param{}}}}}", index + 1);
+                cv.reporter = None;
+            }
+        }
+        generator::render_fragment(&scratch, root)
+    }
+
+    fn anchor_of(&self, id: &str) -> Anchor {
+        if let Some((host, content)) = self.slot_of(id) {
+            return Anchor::Slot(host, content);
+        }
+        match self.get_block(id).and_then(|b| b.parent_id.as_ref()).and_then(|p| self.get_block(p)) {
+            Some(parent) if parent.body_id.as_deref() == Some(id) => Anchor::Body(parent.id.clone()),
+            Some(parent) if parent.child_id.as_deref() == Some(id) => Anchor::Next(parent.id.clone()),
+            _ => Anchor::Free,
+        }
+    }
+
+    // スタックを新しい定義に置き換え、置いたブロックの ID を返す
+    pub fn extract_block(&mut self, root: &str, title: &str, params: &[ExtractParam]) -> Result<String, String> {
+        let title = title.trim();
+        if title.is_empty() {
+            return Err("タイトルを入力してください".to_string());
+        }
+        let block = self.get_block(root).cloned().ok_or_else(|| "ブロックが見つかりません".to_string())?;
+        let is_value = block.block_type == BlockType::Value;
+        let content = params
+            .iter()
+            .enumerate()
+            .map(|(index, param)| BlockContent {
+                id: format!("param{}", index + 1),
+                content: EnumBlockContent::ContentValue(ContentValue {
+                    title: param.title.clone(),
+                    value: param.value.clone(),
+                    placeholder: None,
+                    value_type: param.value_type.clone(),
+                    reporter: None,
+                }),
+            })
+            .collect();
+        let definition = Block {
+            output: self.extract_template(root, params),
+            content,
+            connection: if is_value { Connection::None } else { Connection::Both },
+            output_type: if is_value { types::result_type(self, &block) } else { ValueType::Any },
+            ..Block::new(
+                String::new(),
+                if is_value { BlockType::Value } else { BlockType::Works },
+                title.to_string(),
+                Position { x: 0.0, y: 0.0 },
+            )
+        };
+        let name = format!("custom_{}", Uuid::new_v4().simple());
+        let list = BlockList::new(&self.user_pack.id.clone(), &name, 1, definition);
+        let list_id = list.id.clone();
+
+        // 新しいブロックを置いて定義を保存できてから、元のスタックを取り除く
+        self.add_block_list(list.clone());
+        let Some(id) = self.add_block(&list_id, block.position.x, block.position.y) else {
+            self.remove_block_list(&list_id);
+            return Err("ブロックを置けませんでした".to_string());
+        };
+        if let Err(e) = self.save_custom_block(list) {
+            self.remove_block(&id);
+            self.remove_block_list(&list_id);
+            self.user_pack.blocks.retain(|b| b.id != list_id);
+            return Err(format!("保存に失敗しました: {}", e));
+        }
+
+        // 引数になった値ブロックは残し、それ以外のブロックを取り除く
+        let anchor = self.anchor_of(root);
+        let kept: Vec<String> = params
+            .iter()
+            .filter_map(|p| p.reporter.as_ref())
+            .flat_map(|id| self.tree_ids(id))
+            .collect();
+        for param in params {
+            if let Some(reporter) = &param.reporter {
+                self.detach(reporter);
+            }
+        }
+        self.detach(root);
+        for id in self.tree_ids(root) {
+            if !kept.contains(&id) {
+                self.remove_block(&id);
+                self.block_sizes.remove(&id);
+            }
+        }
+
+        for (index, param) in params.iter().enumerate() {
+            let content_id = format!("param{}", index + 1);
+            match &param.reporter {
+                Some(reporter) => {
+                    if let Err(message) = self.plug(&id, &content_id, reporter) {
+                        self.notify(message);
+                    }
+                }
+                None => self.update_block_content(&id, &content_id, &param.value),
+            }
+        }
+        match anchor {
+            Anchor::Next(parent) => self.attach(&parent, &id),
+            Anchor::Body(parent) => self.attach_body(&parent, &id),
+            Anchor::Slot(host, content) => {
+                if let Err(message) = self.plug(&host, &content, &id) {
+                    self.notify(message);
+                }
+            }
+            Anchor::Free => {}
+        }
+        self.selected = Some(id.clone());
+        Ok(id)
+    }
+}
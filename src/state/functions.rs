@@ -0,0 +1,201 @@
+use crate::models::block::{Block, BlockContent, BlockType, Connection, ContentValue, EnumBlockContent, Position};
+use crate::models::function::Function;
+use crate::models::variable::Variable;
+use crate::state::app_state::{AppState, BlockList};
+use crate::state::variables;
+
+pub const FUNCTION_PACK: &str = "function";
+const CATEGORY: &str = "マイブロック";
+
+pub fn validate(functions: &[Function], variables: &[Variable]) -> Result<(), String> {
+    for (index, function) in functions.iter().enumerate() {
+        variables::validate_name(&function.name, "ブロック名")?;
+        if functions[..index].iter().any(|f| f.name == function.name) {
+            return Err(format!("ブロック名 \"{}\" が重複しています", function.name));
+        }
+        if variables.iter().any(|v| v.name == function.name) {
+            return Err(format!("\"{}\" は変数名として使われています", function.name));
+        }
+        for (i, param) in function.params.iter().enumerate() {
+            variables::validate_name(&param.name, "引数名")?;
+            if function.params[..i].iter().any(|p| p.name == param.name) {
+                return Err(format!("\"{}\" の引数 \"{}\" が重複しています", function.name, param.name));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn list_id(function_id: &str, kind: &str) -> String {
+    format!("{}:{}_{}", FUNCTION_PACK, function_id, kind)
+}
+
+fn function_list(function: &Function, kind: &str, block: Block) -> BlockList {
+    let mut list = BlockList::new(FUNCTION_PACK, &format!("{}_{}", function.id, kind), 1, block);
+    list.category = Some(CATEGORY.to_string());
+    list.tags = vec![function.name.clone()];
+    list
+}
+
+fn kinds(function: &Function) -> Vec<String> {
+    let mut kinds = vec!["define".to_string(), "call".to_string(), "value".to_string()];
+    kinds.extend(function.params.iter().map(|p| format!("param{}", p.id)));
+    kinds
+}
+
+// 引数に ID がなかった頃のデータは、位置から作っていた ID をそのまま引き継ぐ
+fn fill_param_ids(functions: &mut [Function]) {
+    for function in functions {
+        for (i, param) in function.params.iter_mut().enumerate() {
+            if param.id.is_empty() {
+                param.id = i.to_string();
+            }
+        }
+    }
+}
+
+// 引数はフィールドとして並べ、先頭のラベルにブロック名を付ける。空のままだと出力が壊れるので型の既定値を入れておく
+fn arguments(function: &Function) -> (String, Vec<BlockContent>) {
+    let placeholders: Vec<String> = function.params.iter().map(|p| format!("{{{{arg{}}}}}", p.id)).collect();
+    let content = function
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| BlockContent {
+            id: format!("arg{}", param.id),
+            content: EnumBlockContent::ContentValue(ContentValue {
+                title: if i == 0 { format!("{} {}", function.name, param.name) } else { param.name.clone() },
+                value: param.value_type.default_value().to_string(),
+                placeholder: None,
+                value_type: param.value_type.clone(),
+                reporter: None,
+            }),
+        })
+        .collect();
+    (format!("{}({})", function.name, placeholders.join(", ")), content)
+}
+
+// 定義・呼び出し (命令と値)・引数の取得ブロックを作る
+fn function_blocks(function: &Function) -> Vec<BlockList> {
+    let name = &function.name;
+    let signature = function.params.iter().map(|p| p.name.as_str()).collect::<Vec<_>>().join(", ");
+    let (call, content) = arguments(function);
+    let mut lists = vec![
+        function_list(
+            function,
+            "define",
+            Block {
+                connection: Connection::Output,
+                ..Block::new(String::new(), BlockType::Define, format!("定義 {}({})", name, signature), Position { x: 0.0, y: 0.0 })
+            },
+        ),
+        function_list(
+            function,
+            "call",
+            Block {
+                output: format!("{};", call),
+                content: content.clone(),
+                connection: Connection::Both,
+                ..Block::new(String::new(), BlockType::Works, name.clone(), Position { x: 0.0, y: 0.0 })
+            },
+        ),
+        function_list(
+            function,
+            "value",
+            Block {
+                output: call,
+                content,
+                ..Block::new(String::new(), BlockType::Value, name.clone(), Position { x: 0.0, y: 0.0 })
+            },
+        ),
+    ];
+    for param in &function.params {
+        lists.push(function_list(
+            function,
+            &format!("param{}", param.id),
+            Block {
+                output: param.name.clone(),
+                output_type: param.value_type.clone(),
+                ..Block::new(String::new(), BlockType::Value, param.name.clone(), Position { x: 0.0, y: 0.0 })
+            },
+        ));
+    }
+    lists
+}
+
+impl AppState {
+    // 新しく作ったブロックは定義ブロックを空いている場所に置く
+    pub fn set_functions(&mut self, functions: Vec<Function>) -> Result<(), String> {
+        validate(&functions, &self.variables)?;
+        let added: Vec<String> = functions
+            .iter()
+            .filter(|f| !self.functions.iter().any(|old| old.id == f.id))
+            .map(|f| list_id(&f.id, "define"))
+            .collect();
+        self.register_functions(functions);
+        for list in added {
+            let (x, y) = self.free_position();
+            self.add_block(&list, x, y);
+        }
+        Ok(())
+    }
+
+    pub fn register_functions(&mut self, mut functions: Vec<Function>) {
+        fill_param_ids(&mut functions);
+        let stale: Vec<String> = self
+            .functions
+            .iter()
+            .flat_map(|old| match functions.iter().find(|f| f.id == old.id) {
+                Some(function) => kinds(old).into_iter().filter(|k| !kinds(function).contains(k)).map(|k| list_id(&old.id, &k)).collect(),
+                None => kinds(old).iter().map(|k| list_id(&old.id, k)).collect::<Vec<_>>(),
+            })
+            .collect();
+        for id in stale {
+            self.remove_block_list(&id);
+        }
+        for function in &functions {
+            for mut list in function_blocks(function) {
+                match self.get_block_list(&list.id) {
+                    Some(existing) if existing.block == list.block => continue,
+                    Some(existing) => list.version = existing.version + 1,
+                    None => {}
+                }
+                self.add_block_list(list);
+            }
+        }
+        self.functions = functions;
+        self.refresh_function_reach();
+    }
+
+    // ブロックが属するマイブロックと種類 ("define", "call", "value", "param" + 引数の ID)
+    pub fn function_of<'a>(&'a self, block: &'a Block) -> Option<(&'a Function, &'a str)> {
+        let name = block.definition.as_ref()?.id.strip_prefix(FUNCTION_PACK)?.strip_prefix(':')?;
+        let (function_id, kind) = name.rsplit_once('_')?;
+        let function = self.functions.iter().find(|f| f.id == function_id)?;
+        Some((function, kind))
+    }
+
+    // ワークスペースに置かれた定義ブロック (上にあるものから)
+    pub fn define_blocks(&self, function_id: &str) -> Vec<&Block> {
+        let id = list_id(function_id, "define");
+        let mut blocks: Vec<&Block> = self
+            .blocks
+            .values()
+            .filter(|b| b.definition.as_ref().is_some_and(|d| d.id == id))
+            .collect();
+        blocks.sort_by(|a, b| a.position.y.total_cmp(&b.position.y).then(a.position.x.total_cmp(&b.position.x)));
+        blocks
+    }
+
+    // スタックの中で呼び出しているマイブロックと、その呼び出しブロック
+    pub fn called_functions(&self, root: &str) -> Vec<(&Function, String)> {
+        self.tree_ids(root)
+            .iter()
+            .filter_map(|id| self.get_block(id))
+            .filter_map(|block| match self.function_of(block) {
+                Some((function, "call" | "value")) => Some((function, block.id.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,82 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::state::app_state::BlockList;
+use crate::utils::storage;
+
+const USER_PACK_FILE: &str = "packs/user.json";
+const PALETTE_FILE: &str = "palette.json";
+const RECENT_LIMIT: usize = 8;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct LibraryPack {
+    pub id: String,
+    pub name: String,
+    pub blocks: Vec<BlockList>,
+}
+
+impl Default for LibraryPack {
+    fn default() -> Self {
+        Self {
+            id: "user".to_string(),
+            name: "ユーザー".to_string(),
+            blocks: Vec::new(),
+        }
+    }
+}
+
+impl LibraryPack {
+    pub fn load_user() -> Self {
+        let mut pack: Self = storage::read_json(USER_PACK_FILE).unwrap_or_default();
+        for block in pack.blocks.iter_mut().filter(|b| b.id.is_empty()) {
+            block.id = format!("{}:{}", pack.id, block.name);
+        }
+        pack
+    }
+
+    pub fn save_user(&self) -> io::Result<()> {
+        storage::write_json(USER_PACK_FILE, self)
+    }
+
+    pub fn upsert(&mut self, block: BlockList) {
+        match self.blocks.iter_mut().find(|b| b.id == block.id) {
+            Some(existing) => *existing = block,
+            None => self.blocks.push(block),
+        }
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PaletteHistory {
+    pub favorites: Vec<String>,
+    pub recent: Vec<String>,
+}
+
+impl PaletteHistory {
+    pub fn load() -> Self {
+        storage::read_json(PALETTE_FILE).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        storage::write_json(PALETTE_FILE, self)
+    }
+
+    pub fn is_favorite(&self, list_id: &str) -> bool {
+        self.favorites.iter().any(|id| id == list_id)
+    }
+
+    pub fn toggle_favorite(&mut self, list_id: &str) {
+        if self.is_favorite(list_id) {
+            self.favorites.retain(|id| id != list_id);
+        } else {
+            self.favorites.push(list_id.to_string());
+        }
+    }
+
+    pub fn touch(&mut self, list_id: &str) {
+        self.recent.retain(|id| id != list_id);
+        self.recent.insert(0, list_id.to_string());
+        self.recent.truncate(RECENT_LIMIT);
+    }
+}
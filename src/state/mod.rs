@@ -1 +1,12 @@
 pub mod app_state;
+pub mod builtin;
+pub mod extract;
+pub mod functions;
+pub mod library;
+pub mod output;
+pub mod project;
+pub mod reachability;
+pub mod settings;
+pub mod source;
+pub mod stack;
+pub mod variables;
\ No newline at end of file
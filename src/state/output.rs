@@ -0,0 +1,142 @@
+use serde_json::{json, Value};
+
+use crate::models::block::{BlockType, TargetProperty, ValueType};
+use crate::state::app_state::AppState;
+use crate::utils::clipboard;
+use crate::utils::generator;
+use crate::utils::preview::{PreviewError, PreviewInput};
+use crate::utils::types;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Toast {
+    pub id: u64,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreviewContext {
+    pub time: f64,
+    pub value: Value,
+    pub this_layer: Option<String>,
+}
+
+impl Default for PreviewContext {
+    fn default() -> Self {
+        Self {
+            time: 0.0,
+            value: json!(0),
+            this_layer: None,
+        }
+    }
+}
+
+// 値ブロックの上に出す吹き出し (result が None の間は評価中)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueBubble {
+    pub block_id: String,
+    pub result: Option<Result<String, String>>,
+}
+
+impl AppState {
+    pub fn notify(&mut self, message: impl Into<String>) {
+        let id = self.toast.as_ref().map_or(0, |t| t.id + 1);
+        self.toast = Some(Toast {
+            id,
+            message: message.into(),
+        });
+    }
+
+    // 選択中のタブがなければ最初の Flag
+    pub fn active_flag(&self) -> Option<String> {
+        let flags = generator::flag_ids(self);
+        self.active_output
+            .clone()
+            .filter(|id| flags.contains(id))
+            .or_else(|| flags.first().cloned())
+    }
+
+    pub fn preview_input(&self) -> PreviewInput {
+        PreviewInput {
+            time: self.preview.time,
+            value: self.preview.value.clone(),
+            comp: self.comp.clone(),
+            this_layer: self.preview.this_layer.clone(),
+        }
+    }
+
+    pub fn flag_target(&self, flag_id: &str) -> Option<TargetProperty> {
+        self.get_block(flag_id).and_then(|b| b.target.clone())
+    }
+
+    // 貼り付け先が決まっていなければ型は検査しない (プレビューの元の値は試しの入力にすぎない)
+    pub fn target_type(&self, flag_id: &str) -> ValueType {
+        self.flag_target(flag_id).map_or(ValueType::Any, |target| target.value_type())
+    }
+
+    // 元の値が貼り付け先の型に合わなければ既定値を使う
+    pub fn preview_value_for(&self, flag_id: &str) -> Value {
+        match self.flag_target(flag_id) {
+            Some(target) if !target.value_type().accepts(&types::of_value(&self.preview.value)) => {
+                target.default_value()
+            }
+            _ => self.preview.value.clone(),
+        }
+    }
+
+    pub fn set_flag_target(&mut self, flag_id: &str, target: Option<TargetProperty>) {
+        let Some(flag) = self.blocks.get_mut(flag_id).filter(|b| b.block_type == BlockType::Flag) else {
+            return;
+        };
+        flag.target = target;
+        self.preview.value = self.preview_value_for(flag_id);
+    }
+
+    // 値ブロックでなければ None
+    pub fn reporter_expression(&self, block_id: &str) -> Option<Result<String, String>> {
+        self.get_block(block_id)
+            .filter(|b| b.block_type == BlockType::Value)
+            .map(|_| generator::render_expression(self, block_id))
+    }
+
+    // 上限で中断された評価だけを Flag の診断として残す
+    pub fn report_evaluation(&mut self, flag_id: &str, error: Option<&PreviewError>) {
+        match error {
+            Some(error) if error.limit => {
+                self.evaluation_errors.insert(flag_id.to_string(), error.message.clone());
+            }
+            _ => {
+                self.evaluation_errors.remove(flag_id);
+            }
+        }
+    }
+
+    pub fn toggle_breakpoint(&mut self, block_id: &str) {
+        if !self.breakpoints.remove(block_id) {
+            self.breakpoints.insert(block_id.to_string());
+        }
+    }
+
+    pub fn run_flag(&mut self, flag_id: &str) {
+        let Some(generated) = generator::generate(self, flag_id) else {
+            return;
+        };
+        let action = self.settings.flag_action;
+        if action.copies() {
+            // 壊れた式を貼り付けさせないよう、空やエラーのあるときはコピーしない
+            match generated.errors.first() {
+                Some(error) => self.notify(format!("エラーがあるためコピーしませんでした: {}", error.message)),
+                None if generated.code.trim().is_empty() => self.notify("出力が空のためコピーしませんでした"),
+                None => match clipboard::set_text(&generated.code) {
+                    Ok(()) => match &generated.target {
+                        Some(target) => self.notify(format!("コピーしました。{} に貼り付けてください", target.location())),
+                        None => self.notify("クリップボードにコピーしました"),
+                    },
+                    Err(e) => self.notify(format!("コピーに失敗しました: {}", e)),
+                },
+            }
+        }
+        if action.displays() {
+            self.active_output = Some(flag_id.to_string());
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::block::Block;
+use crate::models::comp::MockComp;
+use crate::models::function::Function;
+use crate::models::variable::Variable;
+
+pub const PROJECT_FORMAT_VERSION: u32 = 1;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+    pub format_version: u32,
+    pub blocks: Vec<Block>,
+    #[serde(default)]
+    pub comp: MockComp,
+    #[serde(default)]
+    pub variables: Vec<Variable>,
+    #[serde(default)]
+    pub functions: Vec<Function>,
+}
+
+pub const STACK_FORMAT: &str = "scrpression/stack";
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct StackSnippet {
+    pub format: String,
+    pub format_version: u32,
+    pub blocks: Vec<Block>,
+}
+
+impl StackSnippet {
+    pub fn new(blocks: Vec<Block>) -> Self {
+        Self {
+            format: STACK_FORMAT.to_string(),
+            format_version: PROJECT_FORMAT_VERSION,
+            blocks,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_json(text: &str) -> Option<Self> {
+        serde_json::from_str::<Self>(text.trim())
+            .ok()
+            .filter(|snippet| snippet.format == STACK_FORMAT && !snippet.blocks.is_empty())
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::models::block::{Block, BlockType};
+use crate::state::app_state::AppState;
+
+// Flag の出力に入るブロックの集合。つなぐ・外すたびに動いたスタックの分だけ更新する
+impl AppState {
+    pub fn is_reachable(&self, id: &str) -> bool {
+        self.reachable.contains(id) || self.function_reachable.contains(id)
+    }
+
+    // 読み込みなどでブロックがまとめて入れ替わったときに作り直す
+    pub fn refresh_reachable(&mut self) {
+        let flags: Vec<String> = self
+            .blocks
+            .values()
+            .filter(|b| b.block_type == BlockType::Flag)
+            .map(|b| b.id.clone())
+            .collect();
+        self.reachable = flags.iter().flat_map(|id| self.tree_ids(id)).collect();
+        self.refresh_function_reach();
+    }
+
+    // つないだスタックの親が出力に入っていれば、スタック全体を加える
+    pub fn mark_reachable(&mut self, id: &str) {
+        let Some(block) = self.blocks.get(id) else {
+            return;
+        };
+        let parent = block.parent_id.clone();
+        let is_flag = block.block_type == BlockType::Flag;
+        match block.block_type {
+            // 置いた定義ブロックが既に呼ばれているかは呼び出し側からしかわからない
+            BlockType::Define => self.refresh_function_reach(),
+            _ => {
+                let ids = self.tree_ids(id);
+                if is_flag || parent.as_ref().is_some_and(|p| self.reachable.contains(p)) {
+                    self.reachable.extend(ids.iter().cloned());
+                    self.extend_function_reach(ids, &HashSet::new());
+                } else if parent.as_ref().is_some_and(|p| self.function_reachable.contains(p)) {
+                    self.function_reachable.extend(ids.iter().cloned());
+                    self.extend_function_reach(ids, &HashSet::new());
+                }
+            }
+        }
+        self.refresh_hover();
+    }
+
+    // 外したスタックを取り除く。マイブロックの本体は、呼び出しか本体の一部が外れたときだけ作り直す
+    pub fn unmark_reachable(&mut self, id: &str) {
+        let ids = self.tree_ids(id);
+        let was_reachable = self.reachable.contains(id);
+        if was_reachable {
+            for id in &ids {
+                self.reachable.remove(id);
+            }
+        }
+        let affects_functions = ids.iter().any(|id| {
+            self.function_reachable.contains(id)
+                || (was_reachable && self.blocks.get(id).is_some_and(|b| self.called_function(b).is_some()))
+        });
+        // まだ親とつながったまま呼ばれるので、外すスタックを除いてたどり直す
+        if affects_functions {
+            self.rebuild_function_reach(&ids.iter().cloned().collect());
+        }
+        for id in &ids {
+            self.hovered_stack.remove(id);
+        }
+    }
+
+    fn called_function(&self, block: &Block) -> Option<String> {
+        match self.function_of(block) {
+            Some((function, "call" | "value")) => Some(function.id.clone()),
+            _ => None,
+        }
+    }
+
+    // マイブロックごとの定義ブロック (ワークスペースを一度だけ走査する)
+    fn define_hats(&self) -> HashMap<String, Vec<String>> {
+        let mut hats: HashMap<String, Vec<String>> = HashMap::new();
+        for block in self.blocks.values().filter(|b| b.block_type == BlockType::Define) {
+            if let Some((function, "define")) = self.function_of(block) {
+                hats.entry(function.id.clone()).or_default().push(block.id.clone());
+            }
+        }
+        hats
+    }
+
+    // ids の中から呼ばれているマイブロックの本体を、まだ入っていなければ加える (excluded はたどらない)
+    fn extend_function_reach(&mut self, ids: Vec<String>, excluded: &HashSet<String>) {
+        let mut hats: Option<HashMap<String, Vec<String>>> = None;
+        let mut pending = ids;
+        while let Some(id) = pending.pop() {
+            if excluded.contains(&id) {
+                continue;
+            }
+            let Some(function_id) = self.blocks.get(&id).and_then(|b| self.called_function(b)) else {
+                continue;
+            };
+            let called = hats.get_or_insert_with(|| self.define_hats()).get(&function_id).cloned().unwrap_or_default();
+            for hat in &called {
+                if self.function_reachable.contains(hat) {
+                    continue;
+                }
+                for id in self.tree_ids(hat) {
+                    if !excluded.contains(&id) && self.function_reachable.insert(id.clone()) {
+                        pending.push(id);
+                    }
+                }
+            }
+        }
+    }
+
+    // 出力に入るブロックから呼ばれているマイブロックの本体
+    pub fn refresh_function_reach(&mut self) {
+        self.rebuild_function_reach(&HashSet::new());
+    }
+
+    fn rebuild_function_reach(&mut self, excluded: &HashSet<String>) {
+        self.function_reachable.clear();
+        let ids: Vec<String> = self.reachable.iter().cloned().collect();
+        self.extend_function_reach(ids, excluded);
+    }
+
+    // Flag にポインタを乗せている間、そのスタック全体を強調する
+    pub fn hover_flag(&mut self, id: Option<&str>) {
+        self.hovered_flag = id.map(str::to_string);
+        self.refresh_hover();
+    }
+
+    fn refresh_hover(&mut self) {
+        self.hovered_stack = match &self.hovered_flag {
+            Some(id) => self.tree_ids(id).into_iter().collect(),
+            None => HashSet::new(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::block::{Connection, Position};
+    use crate::models::function::Function;
+    use crate::state::app_state::BlockList;
+    use crate::state::builtin;
+
+    fn state() -> AppState {
+        let mut state = AppState::default();
+        let flag = Block {
+            connection: Connection::Output,
+            ..Block::new(String::new(), BlockType::Flag, "Flag".to_string(), Position { x: 0.0, y: 0.0 })
+        };
+        state.add_block_list(BlockList::new("builtin", "flag", 1, flag));
+        for list in builtin::control_blocks() {
+            state.add_block_list(list);
+        }
+        let function = |id: &str| Function {
+            id: id.to_string(),
+            name: id.to_string(),
+            params: Vec::new(),
+        };
+        state.set_functions(vec![function("f"), function("g")]).unwrap();
+        state
+    }
+
+    // 差分で更新した結果が、最初から数え直した結果と一致するか
+    fn assert_matches_full(state: &AppState, step: &str) {
+        let mut full = state.clone();
+        full.refresh_reachable();
+        assert_eq!(state.reachable, full.reachable, "reachable after {}", step);
+        assert_eq!(state.function_reachable, full.function_reachable, "function_reachable after {}", step);
+    }
+
+    fn remove_stack(state: &mut AppState, id: &str) {
+        state.detach(id);
+        for id in state.tree_ids(id) {
+            state.remove_block(&id);
+        }
+    }
+
+    #[test]
+    fn incremental_updates_match_full_recompute() {
+        let mut state = state();
+        let f = state.define_blocks("f")[0].id.clone();
+        let g = state.define_blocks("g")[0].id.clone();
+        let flag = state.add_block("builtin:flag", 0.0, 0.0).unwrap();
+        let raw = state.add_block("builtin:raw", 0.0, 0.0).unwrap();
+        let body = state.add_block("builtin:raw", 0.0, 0.0).unwrap();
+        let call_f = state.add_block("function:f_call", 0.0, 0.0).unwrap();
+        let call_g = state.add_block("function:g_call", 0.0, 0.0).unwrap();
+        let value_g = state.add_block("function:g_value", 0.0, 0.0).unwrap();
+        let condition = state.add_block("builtin:if", 0.0, 0.0).unwrap();
+        assert_matches_full(&state, "placing blocks");
+
+        state.attach(&flag, &raw);
+        assert_matches_full(&state, "attaching to the flag");
+        state.attach(&f, &body);
+        assert_matches_full(&state, "filling the body of f");
+        state.attach(&raw, &call_f);
+        assert_matches_full(&state, "calling f");
+        state.attach(&body, &call_g);
+        assert_matches_full(&state, "calling g from f");
+        state.plug(&raw, "code", &value_g).unwrap();
+        assert_matches_full(&state, "plugging a call of g");
+        state.detach(&call_g);
+        assert_matches_full(&state, "detaching the call of g");
+        state.detach(&value_g);
+        assert_matches_full(&state, "unplugging the call of g");
+        state.attach(&g, &call_g);
+        assert_matches_full(&state, "calling g from g");
+        state.attach(&flag, &condition);
+        assert_matches_full(&state, "wrapping in an if");
+        state.detach(&raw);
+        state.attach_body(&condition, &raw);
+        assert_matches_full(&state, "moving the stack into the if");
+        state.detach(&condition);
+        assert_matches_full(&state, "detaching the whole stack");
+        state.attach(&flag, &condition);
+        assert_matches_full(&state, "attaching it again");
+        state.detach(&body);
+        assert_matches_full(&state, "detaching the body of f");
+        remove_stack(&mut state, &call_f);
+        assert_matches_full(&state, "removing the call of f");
+        remove_stack(&mut state, &g);
+        assert_matches_full(&state, "removing the define block of g");
+        remove_stack(&mut state, &flag);
+        assert_matches_full(&state, "removing the flag");
+    }
+
+    #[test]
+    fn hovered_stack_follows_attach_and_detach() {
+        let mut state = state();
+        let flag = state.add_block("builtin:flag", 0.0, 0.0).unwrap();
+        let raw = state.add_block("builtin:raw", 0.0, 0.0).unwrap();
+        let next = state.add_block("builtin:raw", 0.0, 0.0).unwrap();
+        state.hover_flag(Some(&flag));
+        state.attach(&flag, &raw);
+        state.attach(&raw, &next);
+        assert_eq!(state.hovered_stack, state.tree_ids(&flag).into_iter().collect());
+        state.detach(&raw);
+        assert_eq!(state.hovered_stack, HashSet::from([flag.clone()]));
+        state.hover_flag(None);
+        assert!(state.hovered_stack.is_empty());
+    }
+}
@@ -0,0 +1,55 @@
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::storage;
+
+const SETTINGS_FILE: &str = "settings.json";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum FlagAction {
+    Copy,
+    Display,
+    #[default]
+    Both,
+}
+
+impl FlagAction {
+    pub fn all() -> Vec<FlagAction> {
+        vec![FlagAction::Copy, FlagAction::Display, FlagAction::Both]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FlagAction::Copy => "クリップボードにコピー",
+            FlagAction::Display => "アウトプットに表示",
+            FlagAction::Both => "コピーして表示",
+        }
+    }
+
+    pub fn copies(&self) -> bool {
+        matches!(self, FlagAction::Copy | FlagAction::Both)
+    }
+
+    pub fn displays(&self) -> bool {
+        matches!(self, FlagAction::Display | FlagAction::Both)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub flag_action: FlagAction,
+    #[serde(default)]
+    pub show_code_editor: bool,
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        storage::read_json(SETTINGS_FILE).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        storage::write_json(SETTINGS_FILE, self)
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use crate::models::block::{Block, EnumBlockContent};
+use crate::state::app_state::AppState;
+use crate::utils::{generator, parser};
+
+// 内側のスタックも含めて上から順に並べた文のブロック (値ブロックは含まない)
+fn statement_order(blocks: &HashMap<String, Block>, root: &str, ids: &mut Vec<String>) {
+    let mut current = Some(root.to_string());
+    while let Some(id) = current {
+        let Some(block) = blocks.get(&id).filter(|_| !ids.contains(&id)) else {
+            break;
+        };
+        ids.push(id);
+        if let Some(body) = &block.body_id {
+            statement_order(blocks, body, ids);
+        }
+        current = block.child_id.clone();
+    }
+}
+
+fn diff_key(block: &Block) -> String {
+    match &block.definition {
+        Some(definition) => definition.id.clone(),
+        None => format!("{:?}:{}", block.block_type, block.output),
+    }
+}
+
+// 同じ定義のブロックを順番を保ったまま最も多く対応づける (古い位置, 新しい位置)
+fn common_blocks(old: &[String], new: &[String]) -> Vec<(usize, usize)> {
+    let mut lengths = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut pairs = Vec::new();
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+impl AppState {
+    // Flag の下のスタックをテキストから作り直す。
+    // 変わっていないブロックは ID ごと残し、位置・サイズ・ブレークポイント・はめ込んだ値ブロックを保つ
+    pub fn replace_stack_source(&mut self, flag_id: &str, source: &str) {
+        let Some(old_child) = self.blocks.get(flag_id).map(|b| b.child_id.clone()) else {
+            return;
+        };
+        let mut old = Vec::new();
+        if let Some(old_child) = &old_child {
+            self.detach(old_child);
+            statement_order(&self.blocks, old_child, &mut old);
+        }
+
+        let parsed = parser::parse_stack(self, source);
+        let root = parsed.iter().find(|b| b.parent_id.is_none()).map(|b| b.id.clone());
+        let mut parsed: HashMap<String, Block> = parsed.into_iter().map(|b| (b.id.clone(), b)).collect();
+        let mut new = Vec::new();
+        if let Some(root) = &root {
+            statement_order(&parsed, root, &mut new);
+        }
+
+        let old_keys: Vec<String> = old.iter().filter_map(|id| self.blocks.get(id)).map(diff_key).collect();
+        let new_keys: Vec<String> = new.iter().filter_map(|id| parsed.get(id)).map(diff_key).collect();
+        let renames: HashMap<String, String> = common_blocks(&old_keys, &new_keys)
+            .into_iter()
+            .map(|(i, j)| (new[j].clone(), old[i].clone()))
+            .collect();
+        let rename = |id: Option<String>| id.map(|id| renames.get(&id).cloned().unwrap_or(id));
+
+        // 対応のつかなかった古いブロックと、使われなくなった値ブロックを消す
+        let mut removed: Vec<String> = old.iter().filter(|id| !renames.values().any(|k| k == *id)).cloned().collect();
+        for (new_id, old_id) in &renames {
+            let (Some(block), Some(previous)) = (parsed.get_mut(new_id), self.blocks.get(old_id)) else {
+                continue;
+            };
+            block.position = previous.position.clone();
+            for content in block.content.iter_mut() {
+                let Some(kept) = previous.content.iter().find(|c| c.id == content.id) else {
+                    continue;
+                };
+                let reporter = match &kept.content {
+                    EnumBlockContent::ContentValue(cv) => cv.reporter.clone(),
+                    _ => None,
+                };
+                let unchanged = match &content.content {
+                    EnumBlockContent::ContentValue(cv) => {
+                        generator::slot_text(self, previous, &content.id).is_some_and(|text| text == cv.value)
+                    }
+                    _ => false,
+                };
+                if unchanged {
+                    *content = kept.clone();
+                } else {
+                    removed.extend(reporter);
+                }
+            }
+        }
+        for id in removed {
+            let reporters: Vec<String> = self
+                .blocks
+                .get(&id)
+                .map(|b| b.reporter_slots().map(|(_, r)| r.to_string()).collect())
+                .unwrap_or_default();
+            let mut ids = vec![id];
+            for reporter in &reporters {
+                ids.extend(self.tree_ids(reporter));
+            }
+            for id in ids {
+                self.remove_block(&id);
+                self.block_sizes.remove(&id);
+            }
+        }
+
+        for (_, mut block) in parsed {
+            block.id = renames.get(&block.id).cloned().unwrap_or(block.id);
+            block.parent_id = rename(block.parent_id);
+            block.child_id = rename(block.child_id);
+            block.body_id = rename(block.body_id);
+            self.blocks.insert(block.id.clone(), block);
+        }
+        if let Some(root) = rename(root) {
+            self.attach(flag_id, &root);
+        }
+        if self.selected.as_ref().is_some_and(|id| !self.blocks.contains_key(id)) {
+            self.selected = None;
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::models::block::{Block, BlockType, Connection, EnumBlockContent};
+use crate::state::app_state::AppState;
+use crate::utils::block::{Size, DEFAULT_MOUTH_HEIGHT, LOOP_ARM_HEIGHT};
+use crate::utils::types;
+
+const SNAP_DISTANCE: f64 = 30.0;
+// 下側の突起分だけ次のブロックを重ねる
+const STACK_OVERLAP: f64 = 16.0;
+const DEFAULT_HEIGHT: f64 = 60.0;
+// C ブロックの内側の突起に合わせる
+const BODY_INDENT: f64 = 10.0;
+const MAX_NESTING: usize = 32;
+const DEFAULT_WIDTH: f64 = 150.0;
+// 読み込んだスタックを既存のブロックの右側に並べる
+const PLACEMENT_GAP: f64 = 40.0;
+const PLACEMENT_ORIGIN: (f64, f64) = (300.0, 80.0);
+const CLICK_DISTANCE: f64 = 3.0;
+// スロットから押し出された値ブロックを置く位置
+const EJECT_OFFSET: (f64, f64) = (20.0, -40.0);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Slot {
+    Next,
+    Body,
+}
+
+// 値ブロックは式の一部なのでスタックには積まない
+fn can_have_child(block: &Block) -> bool {
+    block.block_type != BlockType::Value && matches!(block.connection, Connection::Output | Connection::Both)
+}
+
+fn can_have_parent(block: &Block) -> bool {
+    block.block_type != BlockType::Value && matches!(block.connection, Connection::Input | Connection::Both)
+}
+
+impl AppState {
+    pub fn block_height(&self, id: &str) -> f64 {
+        self.block_sizes.get(id).map(|s| s.height).unwrap_or(DEFAULT_HEIGHT)
+    }
+
+    pub fn mouth_height(&self, id: &str) -> f64 {
+        self.mouth_height_at(id, 0)
+    }
+
+    fn mouth_height_at(&self, id: &str, depth: usize) -> f64 {
+        let body = self.blocks.get(id).filter(|b| b.has_body()).and_then(|b| b.body_id.clone());
+        match body {
+            Some(body) if depth < MAX_NESTING => self.stack_extent_at(&body, depth + 1).max(DEFAULT_MOUTH_HEIGHT),
+            _ => DEFAULT_MOUTH_HEIGHT,
+        }
+    }
+
+    // 次のブロックを置く位置までの高さ (重なり分を含む)
+    fn block_extent_at(&self, id: &str, depth: usize) -> f64 {
+        let height = self.block_height(id);
+        if self.blocks.get(id).is_some_and(|b| b.has_body()) {
+            height + self.mouth_height_at(id, depth) + LOOP_ARM_HEIGHT
+        } else {
+            height
+        }
+    }
+
+    fn stack_extent_at(&self, root: &str, depth: usize) -> f64 {
+        self.stack_ids(root)
+            .iter()
+            .map(|id| self.block_extent_at(id, depth) - STACK_OVERLAP)
+            .sum()
+    }
+
+    pub fn set_block_size(&mut self, id: &str, size: Size) {
+        self.block_sizes.insert(id.to_string(), size);
+        let root = self.stack_root(id);
+        self.layout_stack(&root);
+    }
+
+    pub fn stack_ids(&self, root: &str) -> Vec<String> {
+        let mut ids: Vec<String> = Vec::new();
+        let mut current = Some(root.to_string());
+        while let Some(id) = current {
+            if ids.contains(&id) {
+                break;
+            }
+            let Some(block) = self.blocks.get(&id) else {
+                break;
+            };
+            current = block.child_id.clone();
+            ids.push(id);
+        }
+        ids
+    }
+
+    // 内側のスタックも含めたすべてのブロック
+    pub fn tree_ids(&self, root: &str) -> Vec<String> {
+        let mut ids: Vec<String> = Vec::new();
+        let mut pending = vec![root.to_string()];
+        while let Some(start) = pending.pop() {
+            for id in self.stack_ids(&start) {
+                if ids.contains(&id) {
+                    continue;
+                }
+                if let Some(block) = self.blocks.get(&id) {
+                    pending.extend(block.body_id.clone());
+                    pending.extend(block.reporter_slots().map(|(_, reporter)| reporter.to_string()));
+                }
+                ids.push(id);
+            }
+        }
+        ids
+    }
+
+    // スロットにはめ込まれている値ブロックなら (ホスト, フィールド ID)
+    pub fn slot_of(&self, id: &str) -> Option<(String, String)> {
+        let parent = self.blocks.get(id)?.parent_id.as_ref().and_then(|p| self.blocks.get(p))?;
+        let (content_id, _) = parent.reporter_slots().find(|(_, reporter)| *reporter == id)?;
+        Some((parent.id.clone(), content_id.to_string()))
+    }
+
+    pub fn is_plugged(&self, id: &str) -> bool {
+        self.slot_of(id).is_some()
+    }
+
+    pub fn stack_root(&self, id: &str) -> String {
+        let mut visited = vec![id.to_string()];
+        let mut current = id.to_string();
+        while let Some(parent_id) = self.blocks.get(&current).and_then(|b| b.parent_id.clone()) {
+            if visited.contains(&parent_id) || !self.blocks.contains_key(&parent_id) {
+                break;
+            }
+            visited.push(parent_id.clone());
+            current = parent_id;
+        }
+        current
+    }
+
+    pub fn layout_stack(&mut self, root: &str) {
+        self.layout_stack_at(root, 0);
+    }
+
+    fn layout_stack_at(&mut self, root: &str, depth: usize) {
+        if depth > MAX_NESTING {
+            return;
+        }
+        let ids = self.stack_ids(root);
+        for (i, id) in ids.iter().enumerate() {
+            let Some(block) = self.blocks.get(id) else {
+                continue;
+            };
+            let (x, y) = (block.position.x, block.position.y);
+            if let Some(body) = block.body_id.clone().filter(|_| block.has_body()) {
+                let body_y = y + self.block_height(id) - STACK_OVERLAP;
+                self.update_block_position(&body, x + BODY_INDENT, body_y);
+                self.layout_stack_at(&body, depth + 1);
+            }
+            if let Some(next) = ids.get(i + 1) {
+                let next_y = y + self.block_extent_at(id, depth) - STACK_OVERLAP;
+                self.update_block_position(next, x, next_y);
+            }
+        }
+    }
+
+    pub fn move_stack(&mut self, id: &str, x: f64, y: f64) {
+        self.update_block_position(id, x, y);
+        self.layout_stack(id);
+    }
+
+    pub fn detach(&mut self, id: &str) {
+        self.unmark_reachable(id);
+        let parent_id = self.blocks.get_mut(id).and_then(|b| b.parent_id.take());
+        if let Some(parent) = parent_id.and_then(|p| self.blocks.get_mut(&p)) {
+            if parent.child_id.as_deref() == Some(id) {
+                parent.child_id = None;
+            }
+            if parent.body_id.as_deref() == Some(id) {
+                parent.body_id = None;
+            }
+            for content in parent.content.iter_mut() {
+                if let EnumBlockContent::ContentValue(cv) = &mut content.content {
+                    if cv.reporter.as_deref() == Some(id) {
+                        cv.reporter = None;
+                    }
+                }
+            }
+        }
+    }
+
+    // 型が合わなければはめ込まずに理由を返す
+    pub fn plug(&mut self, host_id: &str, content_id: &str, id: &str) -> Result<(), String> {
+        if self.tree_ids(id).iter().any(|t| t == host_id) {
+            return Err("値ブロックを自分自身の中には入れられません".to_string());
+        }
+        let reporter = self.blocks.get(id).ok_or_else(|| "ブロックが見つかりません".to_string())?;
+        let actual = types::result_type(self, reporter);
+        let host = self.blocks.get(host_id).ok_or_else(|| "ブロックが見つかりません".to_string())?;
+        let slot = host
+            .content
+            .iter()
+            .find(|c| c.id == content_id)
+            .and_then(|c| match &c.content {
+                EnumBlockContent::ContentValue(cv) => Some(cv),
+                _ => None,
+            })
+            .ok_or_else(|| "フィールドが見つかりません".to_string())?;
+        if !slot.value_type.accepts(&actual) {
+            return Err(types::mismatch_message(&slot.title, &slot.value_type, &actual));
+        }
+        let (x, y) = (host.position.x, host.position.y);
+
+        let old = self.blocks.get_mut(host_id).and_then(|host| {
+            host.content.iter_mut().find(|c| c.id == content_id).and_then(|c| match &mut c.content {
+                EnumBlockContent::ContentValue(cv) => cv.reporter.replace(id.to_string()),
+                _ => None,
+            })
+        });
+        if let Some(block) = self.blocks.get_mut(id) {
+            block.parent_id = Some(host_id.to_string());
+        }
+        self.mark_reachable(id);
+        // 入っていた値ブロックはホストの近くに出す
+        if let Some(old) = old.filter(|old| old != id) {
+            self.unmark_reachable(&old);
+            if let Some(block) = self.blocks.get_mut(&old) {
+                block.parent_id = None;
+                block.position.x = x + EJECT_OFFSET.0;
+                block.position.y = y + EJECT_OFFSET.1;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn attach(&mut self, parent_id: &str, id: &str) {
+        self.attach_to(parent_id, id, Slot::Next);
+    }
+
+    pub fn attach_body(&mut self, parent_id: &str, id: &str) {
+        self.attach_to(parent_id, id, Slot::Body);
+    }
+
+    fn attach_to(&mut self, parent_id: &str, id: &str, slot: Slot) {
+        let old_child = self.blocks.get_mut(parent_id).and_then(|p| match slot {
+            Slot::Next => p.child_id.replace(id.to_string()),
+            Slot::Body => p.body_id.replace(id.to_string()),
+        });
+        if let Some(block) = self.blocks.get_mut(id) {
+            block.parent_id = Some(parent_id.to_string());
+        }
+
+        // 既存の子は挿入したスタックの末尾につなぎ直す
+        if let Some(old_child) = old_child.filter(|c| c != id) {
+            let tail = self.stack_ids(id).last().cloned().unwrap_or_else(|| id.to_string());
+            let linkable = self.blocks.get(&tail).is_some_and(can_have_child)
+                && self.blocks.get(&old_child).is_some_and(can_have_parent);
+            if linkable {
+                if let Some(tail_block) = self.blocks.get_mut(&tail) {
+                    tail_block.child_id = Some(old_child.clone());
+                }
+            }
+            if !linkable {
+                self.unmark_reachable(&old_child);
+            }
+            if let Some(child) = self.blocks.get_mut(&old_child) {
+                child.parent_id = if linkable { Some(tail) } else { None };
+            }
+        }
+        self.mark_reachable(id);
+
+        let root = self.stack_root(parent_id);
+        self.layout_stack(&root);
+    }
+
+    pub fn start_drag(&mut self, id: &str, offset_x: f64, offset_y: f64) {
+        // 動かさずに離したら元のスロットに戻す
+        self.hover_slot = self.slot_of(id);
+        self.detach(id);
+        self.selected = Some(id.to_string());
+        self.dragging = Some((id.to_string(), offset_x, offset_y));
+        self.drag_origin = self.blocks.get(id).map(|b| b.position.clone());
+        self.bubble = None;
+        self.context_menu = None;
+    }
+
+    // 動かさずに離したらクリックとみなす
+    pub fn end_drag(&mut self) -> Option<(String, bool)> {
+        let (id, _, _) = self.dragging.take()?;
+        let origin = self.drag_origin.take();
+        let position = self.blocks.get(&id).map(|b| b.position.clone());
+        let clicked = match (origin, position) {
+            (Some(a), Some(b)) => (a.x - b.x).abs() < CLICK_DISTANCE && (a.y - b.y).abs() < CLICK_DISTANCE,
+            _ => false,
+        };
+        Some((id, clicked))
+    }
+
+    pub fn snap_block(&mut self, id: &str) -> bool {
+        let Some(block) = self.blocks.get(id) else {
+            return false;
+        };
+        if block.block_type == BlockType::Value {
+            let Some((host_id, content_id)) = self.hover_slot.take() else {
+                return false;
+            };
+            return match self.plug(&host_id, &content_id, id) {
+                Ok(()) => true,
+                Err(message) => {
+                    self.notify(message);
+                    false
+                }
+            };
+        }
+        if !can_have_parent(block) {
+            return false;
+        }
+        let stack = self.tree_ids(id);
+        let (x, y) = (block.position.x, block.position.y);
+        let distance = |tx: f64, ty: f64| ((tx - x).powi(2) + (ty - y).powi(2)).sqrt();
+        let candidates = self.blocks.values().filter(|b| !stack.contains(&b.id));
+        let next_slots = candidates.clone().filter(|b| can_have_child(b)).map(|b| {
+            let ty = b.position.y + self.block_extent_at(&b.id, 0) - STACK_OVERLAP;
+            (b.id.clone(), Slot::Next, distance(b.position.x, ty))
+        });
+        let body_slots = candidates.filter(|b| b.has_body()).map(|b| {
+            let ty = b.position.y + self.block_height(&b.id) - STACK_OVERLAP;
+            (b.id.clone(), Slot::Body, distance(b.position.x + BODY_INDENT, ty))
+        });
+        let target = next_slots
+            .chain(body_slots)
+            .filter(|(_, _, distance)| *distance < SNAP_DISTANCE)
+            .min_by(|a, b| a.2.total_cmp(&b.2));
+
+        match target {
+            Some((parent_id, slot, _)) => {
+                self.attach_to(&parent_id, id, slot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn copy_stack(&self, root: &str) -> Vec<Block> {
+        self.tree_ids(root)
+            .iter()
+            .filter_map(|id| self.blocks.get(id).cloned())
+            .collect()
+    }
+
+    pub fn free_position(&self) -> (f64, f64) {
+        let right = self
+            .blocks
+            .values()
+            .map(|b| b.position.x + self.block_sizes.get(&b.id).map_or(DEFAULT_WIDTH, |s| s.width))
+            .fold(None, |max: Option<f64>, x| Some(max.map_or(x, |m| m.max(x))));
+        match right {
+            Some(right) => (right + PLACEMENT_GAP, PLACEMENT_ORIGIN.1),
+            None => PLACEMENT_ORIGIN,
+        }
+    }
+
+    // 新しい UUID を振り直して追加し、追加したスタックの先頭 ID を返す
+    pub fn insert_blocks(&mut self, blocks: Vec<Block>, at: Option<(f64, f64)>) -> Vec<String> {
+        let id_map: HashMap<String, String> = blocks
+            .iter()
+            .map(|b| (b.id.clone(), Uuid::new_v4().to_string()))
+            .collect();
+        let is_root = |b: &Block| b.parent_id.as_ref().is_none_or(|p| !id_map.contains_key(p));
+
+        let (dx, dy) = match (at, blocks.iter().find(|b| is_root(b))) {
+            (Some((x, y)), Some(root)) => (x - root.position.x, y - root.position.y),
+            _ => (0.0, 0.0),
+        };
+
+        let mut roots = Vec::new();
+        for mut block in blocks {
+            let root = is_root(&block);
+            block.id = id_map[&block.id].clone();
+            block.parent_id = block.parent_id.and_then(|p| id_map.get(&p).cloned());
+            block.child_id = block.child_id.and_then(|c| id_map.get(&c).cloned());
+            block.body_id = block.body_id.and_then(|c| id_map.get(&c).cloned());
+            for content in block.content.iter_mut() {
+                if let EnumBlockContent::ContentValue(cv) = &mut content.content {
+                    cv.reporter = cv.reporter.take().and_then(|r| id_map.get(&r).cloned());
+                }
+            }
+            block.position.x += dx;
+            block.position.y += dy;
+            if root {
+                roots.push(block.id.clone());
+            }
+            self.blocks.insert(block.id.clone(), block);
+        }
+
+        for root in &roots {
+            self.layout_stack(root);
+            self.mark_reachable(root);
+        }
+        roots
+    }
+}
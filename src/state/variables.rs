@@ -0,0 +1,178 @@
+use crate::models::block::{Block, BlockContent, BlockType, Connection, ContentValue, EnumBlockContent, Position, ValueType};
+use crate::models::function::Function;
+use crate::models::variable::Variable;
+use crate::state::app_state::{AppState, BlockList};
+use crate::utils::{js, types};
+
+pub const VARIABLE_PACK: &str = "variable";
+const CATEGORY: &str = "変数";
+
+// label は "変数名" や "関数名" など
+pub fn validate_name(name: &str, label: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err(format!("{}を入力してください", label));
+    }
+    if !js::is_identifier(name) {
+        return Err(format!("\"{}\" は{}に使えません", name, label));
+    }
+    if js::is_reserved(name) {
+        return Err(format!("\"{}\" は JavaScript の予約語です", name));
+    }
+    if js::is_ae_global(name) {
+        return Err(format!("\"{}\" は AE の式で使われている名前です", name));
+    }
+    Ok(())
+}
+
+pub fn validate(variables: &[Variable], functions: &[Function]) -> Result<(), String> {
+    for (index, variable) in variables.iter().enumerate() {
+        validate_name(&variable.name, "変数名")?;
+        if variables[..index].iter().any(|v| v.name == variable.name) {
+            return Err(format!("変数名 \"{}\" が重複しています", variable.name));
+        }
+        if functions.iter().any(|f| f.name == variable.name) {
+            return Err(format!("\"{}\" はマイブロックの名前として使われています", variable.name));
+        }
+        let initial = types::infer_literal(&variable.initial);
+        if !variable.value_type.accepts(&initial) {
+            return Err(format!(
+                "\"{}\" は{}ですが、初期値が{}です",
+                variable.name,
+                variable.value_type.label(),
+                initial.label()
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn slot(id: &str, title: &str, value: &str, value_type: ValueType) -> BlockContent {
+    BlockContent {
+        id: id.to_string(),
+        content: EnumBlockContent::ContentValue(ContentValue {
+            title: title.to_string(),
+            value: value.to_string(),
+            placeholder: None,
+            value_type,
+            reporter: None,
+        }),
+    }
+}
+
+fn list_id(variable_id: &str, kind: &str) -> String {
+    format!("{}:{}_{}", VARIABLE_PACK, variable_id, kind)
+}
+
+fn variable_list(variable: &Variable, kind: &str, block: Block) -> BlockList {
+    let mut list = BlockList::new(VARIABLE_PACK, &format!("{}_{}", variable.id, kind), 1, block);
+    list.category = Some(CATEGORY.to_string());
+    list.tags = vec![variable.name.clone()];
+    list
+}
+
+// 変数ごとに代入・増減・取得のブロックを作る (名前は出力に直接埋め込む)
+fn variable_blocks(variable: &Variable) -> Vec<BlockList> {
+    let name = &variable.name;
+    let statement = |title: String, output: String, content: Vec<BlockContent>| Block {
+        output,
+        content,
+        connection: Connection::Both,
+        ..Block::new(String::new(), BlockType::Works, title, Position { x: 0.0, y: 0.0 })
+    };
+    let change = match variable.value_type {
+        ValueType::Array2 | ValueType::Array3 | ValueType::Array4 => format!("{} = add({}, {{{{value}}}});", name, name),
+        _ => format!("{} += {{{{value}}}};", name),
+    };
+    vec![
+        variable_list(
+            variable,
+            "set",
+            statement(
+                format!("{} を設定", name),
+                format!("{} = {{{{value}}}};", name),
+                vec![slot("value", &format!("{} =", name), &variable.initial, variable.value_type.clone())],
+            ),
+        ),
+        variable_list(
+            variable,
+            "change",
+            statement(
+                format!("{} を増やす", name),
+                change,
+                vec![slot("value", &format!("{} +=", name), "1", variable.value_type.clone())],
+            ),
+        ),
+        variable_list(
+            variable,
+            "get",
+            Block {
+                output: name.clone(),
+                output_type: variable.value_type.clone(),
+                ..Block::new(String::new(), BlockType::Value, name.clone(), Position { x: 0.0, y: 0.0 })
+            },
+        ),
+    ]
+}
+
+impl AppState {
+    pub fn set_variables(&mut self, variables: Vec<Variable>) -> Result<(), String> {
+        validate(&variables, &self.functions)?;
+        // ボードに置いたブロックが使っている変数は消さない
+        for removed in self.variables.iter().filter(|old| !variables.iter().any(|v| v.id == old.id)) {
+            let count = self
+                .blocks
+                .values()
+                .filter(|b| self.variable_of(b).is_some_and(|v| v.id == removed.id))
+                .count();
+            if count > 0 {
+                return Err(format!(
+                    "変数 \"{}\" はボードの {} 個のブロックで使われているため削除できません",
+                    removed.name, count
+                ));
+            }
+        }
+        self.register_variables(variables);
+        Ok(())
+    }
+
+    // 定義が変わったブロックは版を上げ、配置済みのブロックも書き換える
+    pub fn register_variables(&mut self, variables: Vec<Variable>) {
+        let stale: Vec<String> = self
+            .variables
+            .iter()
+            .filter(|old| !variables.iter().any(|v| v.id == old.id))
+            .flat_map(|old| ["set", "change", "get"].map(|kind| list_id(&old.id, kind)))
+            .collect();
+        for id in stale {
+            self.remove_block_list(&id);
+        }
+        for variable in &variables {
+            for mut list in variable_blocks(variable) {
+                match self.get_block_list(&list.id) {
+                    Some(existing) if existing.block == list.block => continue,
+                    Some(existing) => list.version = existing.version + 1,
+                    None => {}
+                }
+                self.add_block_list(list);
+            }
+        }
+        self.variables = variables;
+    }
+
+    pub fn variable_of(&self, block: &Block) -> Option<&Variable> {
+        let name = block.definition.as_ref()?.id.strip_prefix(VARIABLE_PACK)?.strip_prefix(':')?;
+        let (variable_id, _) = name.rsplit_once('_')?;
+        self.variables.iter().find(|v| v.id == variable_id)
+    }
+
+    // スタックの中で使われている変数 (プロジェクトでの宣言順)
+    pub fn used_variables(&self, root: &str) -> Vec<&Variable> {
+        let used: Vec<&Variable> = self
+            .tree_ids(root)
+            .iter()
+            .filter_map(|id| self.get_block(id))
+            .filter_map(|block| self.variable_of(block))
+            .collect();
+        self.variables.iter().filter(|v| used.iter().any(|u| u.id == v.id)).collect()
+    }
+}
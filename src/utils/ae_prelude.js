@@ -0,0 +1,286 @@
+// After Effects のエクスプレッション環境の簡易スタブ
+// __input は Rust 側から渡される { time, value, comp, thisLayer }
+var __comp = __input.comp;
+var time = __input.time;
+var value = __input.value;
+var inPoint = 0;
+var outPoint = __comp.duration;
+var startTime = 0;
+
+function __isArray(v) { return Array.isArray(v); }
+function __map2(a, b, f) {
+  if (__isArray(a) || __isArray(b)) {
+    var aa = __isArray(a) ? a : [a];
+    var bb = __isArray(b) ? b : [b];
+    var n = Math.max(aa.length, bb.length);
+    var r = [];
+    for (var i = 0; i < n; i++) r.push(f(aa[i] === undefined ? 0 : aa[i], bb[i] === undefined ? 0 : bb[i]));
+    return r;
+  }
+  return f(a, b);
+}
+
+function add(a, b) { return __map2(a, b, function (x, y) { return x + y; }); }
+function sub(a, b) { return __map2(a, b, function (x, y) { return x - y; }); }
+function mul(a, b) { return __isArray(a) ? a.map(function (x) { return x * b; }) : a * b; }
+function div(a, b) { return __isArray(a) ? a.map(function (x) { return x / b; }) : a / b; }
+function clamp(v, min, max) {
+  if (!__isArray(v)) return Math.min(Math.max(v, min), max);
+  return v.map(function (x, i) {
+    return Math.min(Math.max(x, __isArray(min) ? min[i] : min), __isArray(max) ? max[i] : max);
+  });
+}
+function dot(a, b) { var s = 0; for (var i = 0; i < a.length; i++) s += a[i] * (b[i] || 0); return s; }
+function cross(a, b) { return [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]; }
+function length(a, b) {
+  var v = b === undefined ? a : sub(a, b);
+  return __isArray(v) ? Math.sqrt(dot(v, v)) : Math.abs(v);
+}
+function normalize(v) { var l = length(v); return l === 0 ? v : div(v, l); }
+function degreesToRadians(d) { return d * Math.PI / 180; }
+function radiansToDegrees(r) { return r * 180 / Math.PI; }
+function timeToFrames(t, fps) { return Math.floor((t === undefined ? time : t) * (fps || __comp.frame_rate)); }
+function framesToTime(f, fps) { return f / (fps || __comp.frame_rate); }
+function posterizeTime(fps) { time = Math.floor(__input.time * fps) / fps; return value; }
+
+function __interpolate(shape, t, tMin, tMax, v1, v2) {
+  if (v1 === undefined) { v2 = tMax; v1 = tMin; tMax = 1; tMin = 0; }
+  var u = tMax === tMin ? 1 : (t - tMin) / (tMax - tMin);
+  u = Math.min(Math.max(u, 0), 1);
+  u = shape(u);
+  return __map2(v1, v2, function (a, b) { return a + (b - a) * u; });
+}
+function linear(t, a, b, c, d) { return __interpolate(function (u) { return u; }, t, a, b, c, d); }
+function ease(t, a, b, c, d) { return __interpolate(function (u) { return u * u * (3 - 2 * u); }, t, a, b, c, d); }
+function easeIn(t, a, b, c, d) { return __interpolate(function (u) { return u * u; }, t, a, b, c, d); }
+function easeOut(t, a, b, c, d) { return __interpolate(function (u) { return 1 - (1 - u) * (1 - u); }, t, a, b, c, d); }
+
+// 乱数とノイズは毎回同じ結果になるようシード付きで生成する
+var __seed = 1;
+function __hash(n) { var x = Math.sin(n * 12.9898 + __seed * 78.233) * 43758.5453; return x - Math.floor(x); }
+var __randomCount = 0;
+function seedRandom(seed, timeless) { __seed = seed; __randomCount = 0; }
+// グラフ用に同じ環境で時間だけを変えて評価し直す
+function __setTime(t) { __input.time = t; time = t; __seed = 1; __randomCount = 0; }
+function __run(code) { return eval(code); }
+function __random01() { __randomCount += 1; return __hash(__randomCount + (time * 1000)); }
+function random(a, b) {
+  if (a === undefined) return __random01();
+  if (b === undefined) return __isArray(a) ? a.map(function (x) { return __random01() * x; }) : __random01() * a;
+  return __map2(a, b, function (x, y) { return x + __random01() * (y - x); });
+}
+function gaussRandom(a, b) { return random(a, b); }
+function __noise1(x) {
+  var i = Math.floor(x), f = x - i;
+  var u = f * f * (3 - 2 * f);
+  return (__hash(i) * (1 - u) + __hash(i + 1) * u) * 2 - 1;
+}
+function noise(v) { return __noise1(__isArray(v) ? v[0] + (v[1] || 0) * 31.7 : v); }
+function wiggle(freq, amp, octaves, ampMult, t) {
+  octaves = octaves || 1; ampMult = ampMult === undefined ? 0.5 : ampMult; t = t === undefined ? time : t;
+  function offset(dim) {
+    var sum = 0, a = 1, f = freq;
+    for (var o = 0; o < octaves; o++) { sum += __noise1(t * f + dim * 101.3) * a; a *= ampMult; f *= 2; }
+    return sum * amp;
+  }
+  return __isArray(value) ? value.map(function (x, i) { return x + offset(i); }) : value + offset(0);
+}
+function loopOut() { return value; }
+function loopIn() { return value; }
+function loopOutDuration() { return value; }
+function loopInDuration() { return value; }
+function valueAtTime(t) { return value; }
+function velocityAtTime(t) { return __isArray(value) ? value.map(function () { return 0; }) : 0; }
+function smooth() { return value; }
+function hsvToRgb(c) { return c; }
+function rgbToHsl(c) { return c; }
+function hslToRgb(c) { return c; }
+
+// AE のプロパティは演算の中では値として振る舞うので、値そのものにメソッドを生やす
+function __wrap(v) {
+  if (__isArray(v)) return v.slice();
+  if (typeof v === "number") return new Number(v);
+  return new String(v);
+}
+function __keyframes(desc) {
+  return (desc.keyframes || []).slice().sort(function (a, b) { return a.time - b.time; });
+}
+function __valueAt(desc, t) {
+  var keys = __keyframes(desc);
+  if (keys.length === 0) return desc.value;
+  if (t <= keys[0].time) return keys[0].value;
+  var last = keys[keys.length - 1];
+  if (t >= last.time) return last.value;
+  for (var i = 0; i < keys.length - 1; i++) {
+    var a = keys[i], b = keys[i + 1];
+    if (t >= a.time && t <= b.time) return linear(t, a.time, b.time, a.value, b.value);
+  }
+  return last.value;
+}
+function __property(v, desc) {
+  desc = desc || { name: "", value: v, keyframes: [] };
+  var keys = __keyframes(desc);
+  var current = desc.keyframes ? __valueAt(desc, time) : v;
+  var p = __wrap(current);
+  p.value = current;
+  p.numKeys = keys.length;
+  p.valueAtTime = function (t) { return __valueAt(desc, t); };
+  p.velocityAtTime = function (t) {
+    var dt = 1 / __comp.frame_rate;
+    return div(sub(__valueAt(desc, t + dt), __valueAt(desc, t)), dt);
+  };
+  p.key = function (i) {
+    var k = keys[i - 1];
+    if (!k) throw new Error("プロパティ \"" + desc.name + "\" にキーフレーム " + i + " がありません");
+    return { time: k.time, value: k.value, index: i };
+  };
+  p.nearestKey = function (t) {
+    if (keys.length === 0) throw new Error("プロパティ \"" + desc.name + "\" にキーフレームがありません");
+    var best = 0;
+    for (var i = 1; i < keys.length; i++) if (Math.abs(keys[i].time - t) < Math.abs(keys[best].time - t)) best = i;
+    return p.key(best + 1);
+  };
+  p.wiggle = function (f, a) { var saved = value; value = current; var r = wiggle(f, a); value = saved; return r; };
+  // 最初と最後のキーフレームの間を繰り返す (cycle のみ)
+  p.loopOut = function () {
+    if (keys.length < 2) return current;
+    var first = keys[0].time, last = keys[keys.length - 1].time;
+    if (time <= last) return current;
+    return __valueAt(desc, first + ((time - first) % (last - first)));
+  };
+  p.loopIn = function () {
+    if (keys.length < 2) return current;
+    var first = keys[0].time, last = keys[keys.length - 1].time;
+    if (time >= first) return current;
+    return __valueAt(desc, last - ((last - time) % (last - first)));
+  };
+  return p;
+}
+// 結果を JSON に変換できる値に戻す
+function __unwrap(v) {
+  if (v instanceof Number || v instanceof String || v instanceof Boolean) return v.valueOf();
+  if (__isArray(v)) return v.map(__unwrap);
+  return v;
+}
+function __normalizeName(name) { return String(name).replace(/\s+/g, "").toLowerCase(); }
+function __find(items, nameOrIndex) {
+  if (typeof nameOrIndex === "number") return items[nameOrIndex - 1];
+  var key = __normalizeName(nameOrIndex);
+  for (var i = 0; i < items.length; i++) if (__normalizeName(items[i].name) === key) return items[i];
+  return undefined;
+}
+
+var __transformNames = ["anchorPoint", "position", "scale", "rotation", "opacity"];
+
+function __layer(desc, idx) {
+  function property(name) {
+    var found = __find(desc.properties || [], name);
+    if (!found) throw new Error("レイヤー \"" + desc.name + "\" にプロパティ \"" + name + "\" がありません");
+    return __property(found.value, found);
+  }
+  function effect(name) {
+    var found = __find(desc.effects || [], name);
+    if (!found) throw new Error("レイヤー \"" + desc.name + "\" にエフェクト \"" + name + "\" がありません");
+    var fn = function (param) {
+      var p = __find(found.params || [], param);
+      if (!p) throw new Error("エフェクト \"" + found.name + "\" にパラメーター \"" + param + "\" がありません");
+      return __property(p.value, p);
+    };
+    fn.param = fn;
+    return fn;
+  }
+  var transform = function (name) { return property(name); };
+  var layer = {
+    name: desc.name,
+    index: idx,
+    width: __comp.width,
+    height: __comp.height,
+    inPoint: 0,
+    outPoint: __comp.duration,
+    startTime: 0,
+    transform: transform,
+    effect: effect,
+    toComp: function (p) { return p; },
+    fromComp: function (p) { return p; },
+    toWorld: function (p) { return p; },
+    fromWorld: function (p) { return p; }
+  };
+  // 参照したときに初めて存在を確認する
+  __transformNames.forEach(function (name) {
+    Object.defineProperty(transform, name, { get: function () { return property(name); } });
+    Object.defineProperty(layer, name, { get: function () { return property(name); } });
+  });
+  layer.text = {};
+  Object.defineProperty(layer.text, "sourceText", { get: function () { return property("sourceText"); } });
+  return layer;
+}
+
+function __defaultLayer() {
+  var desc = { name: "thisLayer", properties: [], effects: [] };
+  var defaults = [[0, 0], [__comp.width / 2, __comp.height / 2], [100, 100], 0, 100];
+  __transformNames.forEach(function (name, i) { desc.properties.push({ name: name, value: defaults[i], keyframes: [] }); });
+  return __layer(desc, 1);
+}
+
+var thisComp = {
+  name: __comp.name,
+  width: __comp.width,
+  height: __comp.height,
+  duration: __comp.duration,
+  frameDuration: 1 / __comp.frame_rate,
+  numLayers: __comp.layers.length,
+  layer: function (nameOrIndex) {
+    var found = __find(__comp.layers, nameOrIndex);
+    if (!found) throw new Error("コンポジションにレイヤー \"" + nameOrIndex + "\" がありません");
+    return __layer(found, __comp.layers.indexOf(found) + 1);
+  },
+  marker: { numKeys: 0 }
+};
+
+var thisLayer = __input.thisLayer && __find(__comp.layers, __input.thisLayer)
+  ? thisComp.layer(__input.thisLayer)
+  : __defaultLayer();
+var index = thisLayer.index;
+var thisProperty = __property(value);
+var effect = thisLayer.effect;
+var transform = thisLayer.transform;
+var width = __comp.width;
+var height = __comp.height;
+function __global(name) {
+  Object.defineProperty(globalThis, name, { get: function () { return thisLayer[name]; }, configurable: true });
+}
+__transformNames.forEach(__global);
+__global("text");
+function comp(name) { return thisComp; }
+function footage(name) { return { name: name, width: __comp.width, height: __comp.height, duration: __comp.duration }; }
+
+// デバッガ用の記録。ブロックごとに __step が差し込まれる
+var __trace = [];
+var __traceLimit = 1000;
+function __snapshot(v) {
+  if (v instanceof Number || v instanceof String || v instanceof Boolean) return v.valueOf();
+  if (__isArray(v)) return v.map(__snapshot);
+  if (v === null || typeof v === "number" || typeof v === "string" || typeof v === "boolean") return v;
+  if (typeof v === "function") return "[関数]";
+  if (typeof v === "object") return v.name !== undefined ? "[" + v.name + "]" : "[オブジェクト]";
+  return undefined;
+}
+function __step(id, scope) {
+  if (__trace.length >= __traceLimit) throw new Error("ステップ数が上限 (" + __traceLimit + ") を超えました");
+  var vars = [];
+  scope.forEach(function (entry) {
+    try {
+      var v = __snapshot(entry[1]());
+      if (v !== undefined) vars.push([entry[0], v]);
+    } catch (e) {
+      // まだ宣言されていない変数は表示しない
+    }
+  });
+  __trace.push({ block: id, vars: vars });
+}
+function __result(id, v) {
+  for (var i = __trace.length - 1; i >= 0; i--) {
+    if (__trace[i].block === id) { __trace[i].result = __snapshot(v); break; }
+  }
+  return v;
+}
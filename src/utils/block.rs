@@ -1,3 +1,5 @@
+use crate::models::block::{BlockContent, BlockType, EnumBlockContent, Separator};
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PathType {
     Flag,
@@ -86,48 +88,143 @@ pub fn generate_path_string(path_type: &PathType, size: &Size) -> String {
                 size.height - 18.0
             )
         }
-        PathType::Loop | PathType::Value => {
-            format!(
-                "M 4 2 L 14 2 L 14 4 Q 14 8 20 8 L 38 8 Q 42 8 42 4 L 42 2 L {} 2 Q {} 2 {} 4 L {} {} Q {} {} {} {} L 48 {} L 48 {} Q 48 {} 44 {} L 28 {} Q 24 {} 24 {} L 24 {} L 12 {} Q 8 {} 8 {} L 8 {} Q 8 {} 14 {} L {} {} Q {} {} {} {} L {} {} Q {} {} {} {} L 4 {} Q 2 {} 2 {} L 2 4 Q 2 2 4 2 Z",
-                set_width - 4.0,
-                set_width,
-                set_width,
-                set_width,
-                size.height - 18.0,
-                set_width,
-                size.height - 14.0,
-                set_width - 4.0,
-                size.height - 14.0,
-                size.height - 14.0,
-                size.height - 10.0,
-                size.height - 8.0,
-                size.height - 8.0,
-                size.height - 8.0,
-                size.height - 8.0,
-                size.height - 10.0,
-                size.height - 14.0,
-                size.height - 14.0,
-                size.height - 14.0,
-                size.height - 10.0,
-                size.height + 29.0,
-                size.height + 33.0,
-                size.height + 33.0,
-                set_width - 4.0,
-                size.height + 33.0,
-                set_width,
-                size.height + 33.0,
-                set_width,
-                size.height + 37.0,
-                set_width,
-                size.height + 44.0,
-                set_width,
-                size.height + 48.0,
-                set_width - 4.0,
-                size.height + 48.0,
-                size.height + 48.0,
-                size.height + 48.0,
-                size.height + 44.0
-            )
+        PathType::Loop => generate_loop_path_string(size, DEFAULT_MOUTH_HEIGHT),
+        PathType::Value => generate_value_path_string(size),
+    }
+}
+
+// 値ブロックは突起のない角丸の形
+pub fn generate_value_path_string(size: &Size) -> String {
+    let set_width = size.width - 2.0;
+    let bottom = size.height - 14.0;
+    let radius = ((bottom - 2.0) / 2.0).max(0.0);
+    format!(
+        "M {} 2 L {} 2 A {} {} 0 0 1 {} {} L {} {} A {} {} 0 0 1 {} 2 Z",
+        2.0 + radius,
+        set_width - radius,
+        radius,
+        radius,
+        set_width - radius,
+        bottom,
+        2.0 + radius,
+        bottom,
+        radius,
+        radius,
+        2.0 + radius
+    )
+}
+
+pub const DEFAULT_MOUTH_HEIGHT: f64 = 44.0;
+pub const LOOP_ARM_HEIGHT: f64 = 15.0;
+
+// mouth は C ブロックの内側の高さ
+pub fn generate_loop_path_string(size: &Size, mouth: f64) -> String {
+    let set_width = size.width - 2.0;
+    let mouth_bottom = size.height - 14.0 + mouth;
+    let bottom = mouth_bottom + LOOP_ARM_HEIGHT;
+    format!(
+        "M 4 2 L 14 2 L 14 4 Q 14 8 20 8 L 38 8 Q 42 8 42 4 L 42 2 L {} 2 Q {} 2 {} 4 L {} {} Q {} {} {} {} L 48 {} L 48 {} Q 48 {} 44 {} L 28 {} Q 24 {} 24 {} L 24 {} L 12 {} Q 8 {} 8 {} L 8 {} Q 8 {} 14 {} L {} {} Q {} {} {} {} L {} {} Q {} {} {} {} L 40 {} L 40 {} Q 40 {} 36 {} L 20 {} Q 16 {} 16 {} L 16 {} L 4 {} Q 2 {} 2 {} L 2 4 Q 2 2 4 2 Z",
+        set_width - 4.0,
+        set_width,
+        set_width,
+        set_width,
+        size.height - 18.0,
+        set_width,
+        size.height - 14.0,
+        set_width - 4.0,
+        size.height - 14.0,
+        size.height - 14.0,
+        size.height - 10.0,
+        size.height - 8.0,
+        size.height - 8.0,
+        size.height - 8.0,
+        size.height - 8.0,
+        size.height - 10.0,
+        size.height - 14.0,
+        size.height - 14.0,
+        size.height - 14.0,
+        size.height - 10.0,
+        mouth_bottom - 4.0,
+        mouth_bottom,
+        mouth_bottom,
+        set_width - 4.0,
+        mouth_bottom,
+        set_width,
+        mouth_bottom,
+        set_width,
+        mouth_bottom + 4.0,
+        set_width,
+        bottom - 4.0,
+        set_width,
+        bottom,
+        set_width - 4.0,
+        bottom,
+        bottom,
+        bottom + 4.0,
+        bottom + 6.0,
+        bottom + 6.0,
+        bottom + 6.0,
+        bottom + 6.0,
+        bottom + 4.0,
+        bottom,
+        bottom,
+        bottom,
+        bottom - 4.0
+    )
+}
+
+impl From<&BlockType> for PathType {
+    fn from(block_type: &BlockType) -> Self {
+        match block_type {
+            BlockType::Flag => PathType::Flag,
+            BlockType::Works => PathType::Works,
+            BlockType::Move => PathType::Move,
+            BlockType::Composition => PathType::Composition,
+            BlockType::Control => PathType::Loop,
+            BlockType::Raw => PathType::Works,
+            BlockType::Value => PathType::Value,
+            BlockType::Define => PathType::Flag,
         }
     }
-}
\ No newline at end of file
+}
+
+// (fill, stroke)
+pub fn block_colors(block_type: &BlockType) -> (&'static str, &'static str) {
+    match block_type {
+        BlockType::Flag => ("#FFBF00", "#CC9900"),
+        BlockType::Works => ("#9966FF", "#774DCB"),
+        BlockType::Move => ("#5A8DEE", "#3A6BC1"),
+        BlockType::Composition => ("#59C059", "#389438"),
+        BlockType::Control => ("#FFAB19", "#CF8B17"),
+        BlockType::Raw => ("#5C6B7A", "#43505C"),
+        BlockType::Value => ("#0FBD8C", "#0B8E69"),
+        BlockType::Define => ("#FF6680", "#FF3355"),
+    }
+}
+
+pub const CONTENT_GAP: f64 = 5.0;
+pub const SELECTOR_ARROW_WIDTH: f64 = 16.0;
+
+pub fn text_width(text: &str) -> f64 {
+    text.chars().map(|c| if c.is_ascii() { 8.0 } else { 14.0 }).sum()
+}
+
+pub fn input_width(value: &str) -> f64 {
+    (text_width(value) + 16.0).max(60.0)
+}
+
+pub fn content_width(content: &EnumBlockContent) -> f64 {
+    match content {
+        EnumBlockContent::ContentValue(cv) => text_width(&cv.title) + CONTENT_GAP + input_width(&cv.value) + CONTENT_GAP,
+        EnumBlockContent::ContentSelector(cs) => {
+            text_width(&cs.title) + CONTENT_GAP + input_width(&cs.value) + SELECTOR_ARROW_WIDTH + CONTENT_GAP
+        }
+        EnumBlockContent::Separator(Separator::Space) => 8.0,
+        EnumBlockContent::Separator(_) => 0.0,
+    }
+}
+
+pub fn estimate_size(contents: &[BlockContent]) -> Size {
+    let width = contents.iter().fold(40.0, |width, content| width + content_width(&content.content));
+    Size::new(width.max(150.0), 60.0)
+}
@@ -0,0 +1,29 @@
+use std::cell::RefCell;
+
+use arboard::Clipboard;
+
+// Linux では Clipboard を破棄すると内容が消えるため、UI スレッドで保持し続ける
+thread_local! {
+    static CLIPBOARD: RefCell<Option<Clipboard>> = const { RefCell::new(None) };
+}
+
+fn with_clipboard<T>(f: impl FnOnce(&mut Clipboard) -> Result<T, arboard::Error>) -> Result<T, arboard::Error> {
+    CLIPBOARD.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        if cell.is_none() {
+            *cell = Some(Clipboard::new()?);
+        }
+        match cell.as_mut() {
+            Some(clipboard) => f(clipboard),
+            None => Err(arboard::Error::ClipboardNotSupported),
+        }
+    })
+}
+
+pub fn set_text(text: &str) -> Result<(), arboard::Error> {
+    with_clipboard(|clipboard| clipboard.set_text(text))
+}
+
+pub fn get_text() -> Result<String, arboard::Error> {
+    with_clipboard(|clipboard| clipboard.get_text())
+}
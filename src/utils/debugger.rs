@@ -0,0 +1,202 @@
+use std::collections::HashSet;
+
+use serde_json::Value;
+
+use crate::utils::generator::GeneratedCode;
+use crate::utils::js::{self, Token, TokenKind};
+use crate::utils::preview::{PreviewError, PreviewInput};
+use crate::utils::sandbox;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceStep {
+    pub block_id: String,
+    // ブロックを実行する直前の変数の値 (宣言順)
+    pub variables: Vec<(String, Value)>,
+    // 式だけのブロックが返した値
+    pub result: Option<Value>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trace {
+    pub time: f64,
+    pub steps: Vec<TraceStep>,
+    pub outcome: Result<Value, PreviewError>,
+}
+
+impl Trace {
+    // 指定位置より後で最初にブレークポイントのあるステップ (なければ最後まで進む)
+    pub fn next_break(&self, from: usize, breakpoints: &HashSet<String>) -> usize {
+        self.steps
+            .iter()
+            .enumerate()
+            .skip(from + 1)
+            .find(|(_, step)| breakpoints.contains(&step.block_id))
+            .map_or(self.steps.len(), |(index, _)| index)
+    }
+}
+
+const STATEMENT_KEYWORDS: &[&str] = &[
+    "var", "let", "const", "function", "return", "if", "for", "while", "else", "do", "switch", "try",
+    "throw", "break", "continue", "class",
+];
+
+fn significant(tokens: &[Token]) -> Vec<&Token> {
+    tokens.iter().filter(|t| !t.is_trivia()).collect()
+}
+
+// var / let / const で宣言された名前を出現順に集める (分割代入は対象外)
+fn declared_names(code: &str) -> Vec<String> {
+    let tokens = js::tokenize(code);
+    let tokens = significant(&tokens);
+    let mut names: Vec<String> = Vec::new();
+    let mut push = |name: &str| {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    };
+    for (i, token) in tokens.iter().enumerate() {
+        if !(token.is_keyword("var") || token.is_keyword("let") || token.is_keyword("const")) {
+            continue;
+        }
+        let mut depth = 0i32;
+        let mut expect_name = true;
+        for token in &tokens[i + 1..] {
+            if expect_name {
+                expect_name = false;
+                if token.kind == TokenKind::Identifier {
+                    push(&token.text);
+                    continue;
+                }
+            }
+            match token.text.as_str() {
+                "(" | "[" | "{" if token.kind == TokenKind::Punctuation => depth += 1,
+                ")" | "]" | "}" if token.kind == TokenKind::Punctuation => depth -= 1,
+                "," if depth == 0 => expect_name = true,
+                ";" if depth == 0 => break,
+                _ => {}
+            }
+            if depth < 0 {
+                break;
+            }
+        }
+    }
+    names
+}
+
+// 1 行の式文なら結果を記録できるよう __result で包む
+fn wrap_expression(block_id: &str, text: &str) -> Option<String> {
+    if text.contains('\n') {
+        return None;
+    }
+    let tokens = js::tokenize(text);
+    if tokens.iter().any(|t| t.kind == TokenKind::Comment) {
+        return None;
+    }
+    let tokens = significant(&tokens);
+    let first = tokens.first()?;
+    if STATEMENT_KEYWORDS.iter().any(|k| first.is_keyword(k)) || first.is_punct("{") || first.is_punct("}") {
+        return None;
+    }
+    let (last, rest) = tokens.split_last()?;
+    if rest.iter().any(|t| t.is_punct(";")) {
+        return None;
+    }
+    let expression = if last.is_punct(";") { &text[..last.start] } else { text };
+    Some(format!("__result({:?}, ({}));", block_id, expression.trim()))
+}
+
+// 波括弧を数えて、行の後ろのスコープに進める
+fn enter_scopes(text: &str, scopes: &mut Vec<usize>, count: &mut usize) {
+    for token in significant(&js::tokenize(text)) {
+        if token.is_punct("{") {
+            scopes.push(*count);
+            *count += 1;
+        } else if token.is_punct("}") && scopes.len() > 1 {
+            scopes.pop();
+        }
+    }
+}
+
+// 変数を読む関数の配列はスコープの先頭で一度だけ作り、各 __step からはそれを渡す
+fn push_step(lines: &mut Vec<String>, block_id: &str, scope: usize, declared: &mut HashSet<usize>, getters: &str) {
+    if declared.insert(scope) {
+        lines.push(format!("var __scope{} = [{}];", scope, getters));
+    }
+    lines.push(format!("__step({:?}, __scope{});", block_id, scope));
+}
+
+// ブロックごとに __step を差し込んだコードを作る。C ブロックの閉じ括弧側はそのまま
+pub fn instrument(generated: &GeneratedCode) -> String {
+    let getters = declared_names(&generated.code)
+        .iter()
+        .map(|name| format!("[{:?}, function () {{ return {}; }}]", name, name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut seen = HashSet::new();
+    let mut declared = HashSet::new();
+    let mut scopes = vec![0];
+    let mut count = 1;
+    let mut lines = Vec::new();
+    for span in &generated.spans {
+        let text = &generated.code[span.start..span.end];
+        let scope = scopes.last().copied().unwrap_or(0);
+        enter_scopes(text, &mut scopes, &mut count);
+        if !seen.insert(span.block_id.as_str()) {
+            lines.push(text.to_string());
+            continue;
+        }
+        let tokens = js::tokenize(text);
+        let starts_with_else = significant(&tokens).first().is_some_and(|t| t.is_keyword("else"));
+        if starts_with_else {
+            // if の閉じ括弧との間に文を挟めないので、本体の先頭で記録する
+            lines.push(text.to_string());
+            let scope = scopes.last().copied().unwrap_or(0);
+            push_step(&mut lines, &span.block_id, scope, &mut declared, &getters);
+        } else {
+            push_step(&mut lines, &span.block_id, scope, &mut declared, &getters);
+            lines.push(wrap_expression(&span.block_id, text).unwrap_or_else(|| text.to_string()));
+        }
+    }
+    lines.join("\n")
+}
+
+fn parse_steps(trace: &Value) -> Vec<TraceStep> {
+    let Some(items) = trace.as_array() else {
+        return Vec::new();
+    };
+    items
+        .iter()
+        .filter_map(|item| {
+            let block_id = item.get("block")?.as_str()?.to_string();
+            let variables = item
+                .get("vars")
+                .and_then(Value::as_array)
+                .map(|vars| {
+                    vars.iter()
+                        .filter_map(|pair| Some((pair.get(0)?.as_str()?.to_string(), pair.get(1)?.clone())))
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some(TraceStep {
+                block_id,
+                variables,
+                result: item.get("result").cloned(),
+            })
+        })
+        .collect()
+}
+
+pub fn from_recording(time: f64, outcome: Result<Value, PreviewError>, recording: &Value) -> Trace {
+    Trace {
+        time,
+        steps: parse_steps(recording),
+        outcome,
+    }
+}
+
+pub async fn trace(generated: &GeneratedCode, input: PreviewInput) -> Trace {
+    let code = instrument(generated);
+    let time = input.time;
+    let (outcome, recording) = sandbox::evaluate_traced(code, input).await;
+    from_recording(time, outcome, &recording)
+}
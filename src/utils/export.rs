@@ -0,0 +1,496 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use image::RgbaImage;
+use tracing::warn;
+
+use crate::models::block::{Block, EnumBlockContent, Separator};
+use crate::state::app_state::AppState;
+use crate::utils::block::{
+    block_colors, estimate_size, generate_loop_path_string, generate_path_string, input_width, text_width, PathType,
+    CONTENT_GAP, LOOP_ARM_HEIGHT, SELECTOR_ARROW_WIDTH,
+};
+
+const MARGIN: f64 = 16.0;
+const SHADOW_OFFSET: f64 = 4.0;
+const STROKE_WIDTH: f64 = 2.5;
+const INPUT_STROKE_WIDTH: f64 = 2.0;
+const FONT_SIZE: f64 = 14.0;
+const FONT_FAMILY: &str = "Noto Sans JP, sans-serif";
+const PILL_HEIGHT: f64 = 24.0;
+const TEXT_COLOR: &str = "#FFFFFF";
+const INPUT_COLOR: &str = "#FFFFFF";
+const INPUT_TEXT_COLOR: &str = "#333333";
+const SUBSAMPLES: usize = 4;
+const CURVE_SEGMENTS: usize = 8;
+
+// PNG に文字を描くためのフォント候補 (見つからなければ文字なしで書き出す)
+const FONT_CANDIDATES: &[&str] = &[
+    "C:\\Windows\\Fonts\\YuGothM.ttc",
+    "C:\\Windows\\Fonts\\meiryo.ttc",
+    "C:\\Windows\\Fonts\\msgothic.ttc",
+    "/System/Library/Fonts/ヒラギノ角ゴシック W3.ttc",
+    "/System/Library/Fonts/Helvetica.ttc",
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+];
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Image(image::ImageError),
+    UnsupportedFormat(String),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Io(e) => write!(f, "{}", e),
+            ExportError::Image(e) => write!(f, "{}", e),
+            ExportError::UnsupportedFormat(ext) => write!(f, "unsupported export format: {}", ext),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+enum Shape {
+    Path {
+        d: String,
+        x: f64,
+        y: f64,
+        fill: &'static str,
+        stroke: Option<(&'static str, f64)>,
+    },
+    Text {
+        x: f64,
+        y: f64,
+        text: String,
+        color: &'static str,
+    },
+}
+
+pub struct Scene {
+    width: f64,
+    height: f64,
+    shapes: Vec<Shape>,
+}
+
+fn rounded_rect_path(width: f64, height: f64, radius: f64) -> String {
+    format!(
+        "M {r} 0 L {} 0 Q {w} 0 {w} {r} L {w} {} Q {w} {h} {} {h} L {r} {h} Q 0 {h} 0 {} L 0 {r} Q 0 0 {r} 0 Z",
+        width - radius,
+        height - radius,
+        width - radius,
+        height - radius,
+        r = radius,
+        w = width,
+        h = height,
+    )
+}
+
+impl Scene {
+    pub fn from_blocks(blocks: &[Block]) -> Self {
+        // C ブロックの内側の高さを求めるためだけに使う
+        let layout = AppState {
+            blocks: blocks.iter().map(|b| (b.id.clone(), b.clone())).collect(),
+            ..AppState::default()
+        };
+        // スロットにはめ込まれた値ブロックはホストのフィールドに名前だけ描く
+        let mut blocks: Vec<&Block> = blocks.iter().filter(|b| !layout.is_plugged(&b.id)).collect();
+        blocks.sort_by(|a, b| {
+            a.position
+                .y
+                .total_cmp(&b.position.y)
+                .then(a.position.x.total_cmp(&b.position.x))
+        });
+        let min_x = blocks.iter().map(|b| b.position.x).fold(f64::INFINITY, f64::min);
+        let min_y = blocks.iter().map(|b| b.position.y).fold(f64::INFINITY, f64::min);
+
+        let mut scene = Scene {
+            width: 0.0,
+            height: 0.0,
+            shapes: Vec::new(),
+        };
+        for block in blocks {
+            let size = estimate_size(&block.content);
+            let (fill, stroke) = block_colors(&block.block_type);
+            let (d, height) = if block.has_body() {
+                let mouth = layout.mouth_height(&block.id);
+                (generate_loop_path_string(&size, mouth), size.height + mouth + LOOP_ARM_HEIGHT)
+            } else {
+                (generate_path_string(&PathType::from(&block.block_type), &size), size.height)
+            };
+            let x = block.position.x - min_x + MARGIN;
+            let y = block.position.y - min_y + MARGIN;
+
+            scene.shapes.push(Shape::Path {
+                d: d.clone(),
+                x,
+                y: y + SHADOW_OFFSET,
+                fill: stroke,
+                stroke: None,
+            });
+            scene.shapes.push(Shape::Path {
+                d,
+                x,
+                y,
+                fill,
+                stroke: Some((stroke, STROKE_WIDTH)),
+            });
+            scene.push_contents(&layout, block, x, y, size.height, stroke);
+
+            scene.width = scene.width.max(x + size.width + MARGIN);
+            scene.height = scene.height.max(y + height + SHADOW_OFFSET + MARGIN);
+        }
+        scene
+    }
+
+    fn push_contents(&mut self, layout: &AppState, block: &Block, x: f64, y: f64, height: f64, stroke: &'static str) {
+        let center_y = y + height / 2.0 - 6.0;
+        let baseline = center_y + FONT_SIZE / 2.0 - 2.0;
+        let mut cursor = x + 20.0;
+
+        for content in &block.content {
+            let reporter = match &content.content {
+                EnumBlockContent::ContentValue(cv) => cv.reporter.as_ref().and_then(|id| layout.get_block(id)),
+                _ => None,
+            };
+            let (title, value, extra) = match &content.content {
+                EnumBlockContent::ContentValue(cv) => (&cv.title, reporter.map_or(&cv.value, |r| &r.title), 0.0),
+                EnumBlockContent::ContentSelector(cs) => (&cs.title, &cs.value, SELECTOR_ARROW_WIDTH),
+                EnumBlockContent::Separator(Separator::Space) => {
+                    cursor += 8.0;
+                    continue;
+                }
+                EnumBlockContent::Separator(_) => continue,
+            };
+
+            self.shapes.push(Shape::Text {
+                x: cursor,
+                y: baseline,
+                text: title.clone(),
+                color: TEXT_COLOR,
+            });
+            cursor += text_width(title) + CONTENT_GAP;
+
+            let width = input_width(value) + extra;
+            self.shapes.push(Shape::Path {
+                d: rounded_rect_path(width, PILL_HEIGHT, PILL_HEIGHT / 2.0),
+                x: cursor,
+                y: center_y - PILL_HEIGHT / 2.0,
+                fill: INPUT_COLOR,
+                stroke: Some((stroke, INPUT_STROKE_WIDTH)),
+            });
+            let text = if extra > 0.0 { format!("{} ▾", value) } else { value.clone() };
+            self.shapes.push(Shape::Text {
+                x: cursor + 8.0,
+                y: baseline,
+                text,
+                color: INPUT_TEXT_COLOR,
+            });
+            cursor += width + CONTENT_GAP;
+        }
+    }
+
+    pub fn to_svg(&self) -> String {
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n",
+            w = self.width,
+            h = self.height
+        );
+        for shape in &self.shapes {
+            match shape {
+                Shape::Path { d, x, y, fill, stroke } => {
+                    let stroke = stroke
+                        .map(|(color, width)| format!(" stroke=\"{}\" stroke-width=\"{}\"", color, width))
+                        .unwrap_or_default();
+                    svg.push_str(&format!(
+                        "  <path transform=\"translate({} {})\" d=\"{}\" fill=\"{}\"{}/>\n",
+                        x, y, d, fill, stroke
+                    ));
+                }
+                Shape::Text { x, y, text, color } => {
+                    svg.push_str(&format!(
+                        "  <text x=\"{}\" y=\"{}\" font-family=\"{}\" font-size=\"{}\" font-weight=\"500\" fill=\"{}\">{}</text>\n",
+                        x,
+                        y,
+                        FONT_FAMILY,
+                        FONT_SIZE,
+                        color,
+                        escape_xml(text)
+                    ));
+                }
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    pub fn to_png(&self, scale: f64) -> RgbaImage {
+        let width = (self.width * scale).ceil().max(1.0) as u32;
+        let height = (self.height * scale).ceil().max(1.0) as u32;
+        let mut image = RgbaImage::new(width, height);
+        let font = load_font();
+        if font.is_none() {
+            warn!("No font found; exporting PNG without text");
+        }
+
+        for shape in &self.shapes {
+            match shape {
+                Shape::Path { d, x, y, fill, stroke } => {
+                    let polygons = flatten_path(d, *x, *y, scale);
+                    fill_polygons(&mut image, &polygons, parse_color(fill));
+                    if let Some((color, stroke_width)) = stroke {
+                        stroke_polygons(&mut image, &polygons, parse_color(color), stroke_width * scale);
+                    }
+                }
+                Shape::Text { x, y, text, color } => {
+                    if let Some(font) = &font {
+                        draw_text(&mut image, font, text, x * scale, y * scale, FONT_SIZE * scale, parse_color(color));
+                    }
+                }
+            }
+        }
+        image
+    }
+}
+
+pub fn save(blocks: &[Block], path: &Path, scale: f64) -> Result<(), ExportError> {
+    let scene = Scene::from_blocks(blocks);
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    match extension.as_str() {
+        "svg" => fs::write(path, scene.to_svg()).map_err(ExportError::Io),
+        "png" => scene.to_png(scale).save(path).map_err(ExportError::Image),
+        _ => Err(ExportError::UnsupportedFormat(extension)),
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn parse_color(hex: &str) -> [u8; 3] {
+    let hex = hex.trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .unwrap_or(0)
+    };
+    [channel(0), channel(2), channel(4)]
+}
+
+fn load_font() -> Option<FontVec> {
+    FONT_CANDIDATES.iter().find_map(|path| {
+        let bytes = fs::read(path).ok()?;
+        FontVec::try_from_vec_and_index(bytes, 0).ok()
+    })
+}
+
+// generate_path_string が出力する M / L / Q / Z のみを扱う
+fn flatten_path(d: &str, offset_x: f64, offset_y: f64, scale: f64) -> Vec<Vec<(f64, f64)>> {
+    let tokens: Vec<&str> = d.split_whitespace().collect();
+    let to_point = |x: f64, y: f64| ((x + offset_x) * scale, (y + offset_y) * scale);
+    let number = |i: usize| tokens.get(i).and_then(|t| t.parse::<f64>().ok()).unwrap_or(0.0);
+
+    let mut polygons = Vec::new();
+    let mut current: Vec<(f64, f64)> = Vec::new();
+    let mut last = (0.0, 0.0);
+    let mut i = 0;
+    while i < tokens.len() {
+        match tokens[i] {
+            "M" => {
+                if current.len() > 2 {
+                    polygons.push(std::mem::take(&mut current));
+                }
+                current.clear();
+                last = (number(i + 1), number(i + 2));
+                current.push(to_point(last.0, last.1));
+                i += 3;
+            }
+            "L" => {
+                last = (number(i + 1), number(i + 2));
+                current.push(to_point(last.0, last.1));
+                i += 3;
+            }
+            "Q" => {
+                let control = (number(i + 1), number(i + 2));
+                let end = (number(i + 3), number(i + 4));
+                for step in 1..=CURVE_SEGMENTS {
+                    let t = step as f64 / CURVE_SEGMENTS as f64;
+                    let mt = 1.0 - t;
+                    let x = mt * mt * last.0 + 2.0 * mt * t * control.0 + t * t * end.0;
+                    let y = mt * mt * last.1 + 2.0 * mt * t * control.1 + t * t * end.1;
+                    current.push(to_point(x, y));
+                }
+                last = end;
+                i += 5;
+            }
+            "Z" => {
+                if current.len() > 2 {
+                    polygons.push(std::mem::take(&mut current));
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    if current.len() > 2 {
+        polygons.push(current);
+    }
+    polygons
+}
+
+fn blend(image: &mut RgbaImage, x: i64, y: i64, color: [u8; 3], coverage: f32) {
+    if x < 0 || y < 0 || x >= image.width() as i64 || y >= image.height() as i64 {
+        return;
+    }
+    let alpha = coverage.clamp(0.0, 1.0);
+    if alpha <= 0.0 {
+        return;
+    }
+    let pixel = image.get_pixel_mut(x as u32, y as u32);
+    let dst_alpha = pixel[3] as f32 / 255.0;
+    let out_alpha = alpha + dst_alpha * (1.0 - alpha);
+    for i in 0..3 {
+        let value = (color[i] as f32 * alpha + pixel[i] as f32 * dst_alpha * (1.0 - alpha)) / out_alpha;
+        pixel[i] = value.round() as u8;
+    }
+    pixel[3] = (out_alpha * 255.0).round() as u8;
+}
+
+fn bounds(polygons: &[Vec<(f64, f64)>], padding: f64) -> Option<(i64, i64, i64, i64)> {
+    let points = polygons.iter().flatten();
+    let min_x = points.clone().map(|p| p.0).fold(f64::INFINITY, f64::min);
+    let max_x = points.clone().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+    let min_y = points.clone().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let max_y = points.map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    if !min_x.is_finite() || !min_y.is_finite() {
+        return None;
+    }
+    Some((
+        (min_x - padding).floor() as i64,
+        (min_y - padding).floor() as i64,
+        (max_x + padding).ceil() as i64,
+        (max_y + padding).ceil() as i64,
+    ))
+}
+
+fn fill_polygons(image: &mut RgbaImage, polygons: &[Vec<(f64, f64)>], color: [u8; 3]) {
+    let Some((min_x, min_y, max_x, max_y)) = bounds(polygons, 0.0) else {
+        return;
+    };
+    let min_x = min_x.max(0);
+    let max_x = max_x.min(image.width() as i64 - 1);
+    if max_x < min_x {
+        return;
+    }
+    let mut coverage = vec![0.0f32; (max_x - min_x + 1) as usize];
+    let mut crossings: Vec<f64> = Vec::new();
+
+    for py in min_y.max(0)..=max_y.min(image.height() as i64 - 1) {
+        coverage.fill(0.0);
+        for sample in 0..SUBSAMPLES {
+            let sy = py as f64 + (sample as f64 + 0.5) / SUBSAMPLES as f64;
+            crossings.clear();
+            for polygon in polygons {
+                for (i, &(x0, y0)) in polygon.iter().enumerate() {
+                    let (x1, y1) = polygon[(i + 1) % polygon.len()];
+                    if (y0 <= sy && y1 > sy) || (y1 <= sy && y0 > sy) {
+                        crossings.push(x0 + (sy - y0) / (y1 - y0) * (x1 - x0));
+                    }
+                }
+            }
+            crossings.sort_by(f64::total_cmp);
+            for span in crossings.chunks_exact(2) {
+                let (start, end) = (span[0], span[1]);
+                let first = (start.floor() as i64).max(min_x);
+                let last = (end.ceil() as i64 - 1).min(max_x);
+                for px in first..=last {
+                    let overlap = end.min(px as f64 + 1.0) - start.max(px as f64);
+                    if overlap > 0.0 {
+                        coverage[(px - min_x) as usize] += overlap as f32 / SUBSAMPLES as f32;
+                    }
+                }
+            }
+        }
+        for (i, value) in coverage.iter().enumerate() {
+            blend(image, min_x + i as i64, py, color, *value);
+        }
+    }
+}
+
+fn distance_to_segment(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length).clamp(0.0, 1.0)
+    };
+    let (cx, cy) = (a.0 + t * dx, a.1 + t * dy);
+    ((p.0 - cx).powi(2) + (p.1 - cy).powi(2)).sqrt()
+}
+
+fn stroke_polygons(image: &mut RgbaImage, polygons: &[Vec<(f64, f64)>], color: [u8; 3], width: f64) {
+    let half = width / 2.0;
+    let Some((min_x, min_y, max_x, max_y)) = bounds(polygons, half + 1.0) else {
+        return;
+    };
+    let columns = (max_x - min_x + 1) as usize;
+    let rows = (max_y - min_y + 1) as usize;
+    let mut coverage = vec![0.0f32; columns * rows];
+
+    for polygon in polygons {
+        for (i, &a) in polygon.iter().enumerate() {
+            let b = polygon[(i + 1) % polygon.len()];
+            let x0 = (a.0.min(b.0) - half - 1.0).floor() as i64;
+            let x1 = (a.0.max(b.0) + half + 1.0).ceil() as i64;
+            let y0 = (a.1.min(b.1) - half - 1.0).floor() as i64;
+            let y1 = (a.1.max(b.1) + half + 1.0).ceil() as i64;
+            for py in y0.max(min_y)..=y1.min(max_y) {
+                for px in x0.max(min_x)..=x1.min(max_x) {
+                    let distance = distance_to_segment((px as f64 + 0.5, py as f64 + 0.5), a, b);
+                    let value = (half + 0.5 - distance).clamp(0.0, 1.0) as f32;
+                    let index = (py - min_y) as usize * columns + (px - min_x) as usize;
+                    coverage[index] = coverage[index].max(value);
+                }
+            }
+        }
+    }
+
+    for (index, value) in coverage.iter().enumerate() {
+        let x = min_x + (index % columns) as i64;
+        let y = min_y + (index / columns) as i64;
+        blend(image, x, y, color, *value);
+    }
+}
+
+fn draw_text(image: &mut RgbaImage, font: &FontVec, text: &str, x: f64, baseline: f64, size: f64, color: [u8; 3]) {
+    let scaled = font.as_scaled(PxScale::from(size as f32));
+    let mut caret = x as f32;
+    for c in text.chars() {
+        let mut glyph = scaled.scaled_glyph(c);
+        glyph.position = point(caret, baseline as f32);
+        caret += scaled.h_advance(glyph.id);
+        if let Some(outlined) = font.outline_glyph(glyph) {
+            let glyph_bounds = outlined.px_bounds();
+            outlined.draw(|gx, gy, value| {
+                let px = glyph_bounds.min.x as i64 + gx as i64;
+                let py = glyph_bounds.min.y as i64 + gy as i64;
+                blend(image, px, py, color, value);
+            });
+        }
+    }
+}
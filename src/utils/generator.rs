@@ -0,0 +1,439 @@
+use crate::models::block::{Block, BlockType, EnumBlockContent, TargetProperty, ValueType};
+use crate::state::app_state::{AppState, DefinitionStatus};
+use crate::utils::{template, types};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratorError {
+    pub block_id: String,
+    pub message: String,
+}
+
+// 出力コード中のバイト範囲と生成元ブロックの対応
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceSpan {
+    pub start: usize,
+    pub end: usize,
+    pub block_id: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeneratedCode {
+    pub flag_id: String,
+    pub flag_title: String,
+    pub target: Option<TargetProperty>,
+    pub code: String,
+    // code のうち Flag のスタックが始まる位置。手前の変数宣言と関数定義はコードの編集の対象にしない
+    pub stack_start: usize,
+    // 文のブロックごとの範囲 (デバッガはこの単位で止まる)
+    pub spans: Vec<SourceSpan>,
+    // はめ込まれた値ブロックごとの範囲
+    pub expressions: Vec<SourceSpan>,
+    pub errors: Vec<GeneratorError>,
+    // 出力はできるが確認してほしいこと (再帰呼び出しなど)
+    pub warnings: Vec<GeneratorError>,
+}
+
+impl GeneratedCode {
+    pub fn preamble(&self) -> &str {
+        self.code[..self.stack_start].trim_end_matches('\n')
+    }
+
+    pub fn stack_code(&self) -> &str {
+        self.code[self.stack_start..].trim_start_matches('\n')
+    }
+
+    pub fn line_of(&self, offset: usize) -> usize {
+        self.code[..offset.min(self.code.len())].matches('\n').count()
+    }
+
+    pub fn block_at_line(&self, line: usize) -> Option<&str> {
+        self.spans
+            .iter()
+            .find(|span| self.line_of(span.start) <= line && line <= self.line_of(span.end))
+            .map(|span| span.block_id.as_str())
+    }
+
+    pub fn lines_for_block(&self, block_id: &str) -> Vec<usize> {
+        self.spans
+            .iter()
+            .chain(&self.expressions)
+            .filter(|span| span.block_id == block_id)
+            .flat_map(|span| self.line_of(span.start)..=self.line_of(span.end))
+            .collect()
+    }
+}
+
+const INDENT: &str = "    ";
+const MAX_NESTING: usize = 32;
+
+#[derive(Default)]
+struct CodeBuilder {
+    code: String,
+    spans: Vec<SourceSpan>,
+    expressions: Vec<SourceSpan>,
+}
+
+impl CodeBuilder {
+    fn push_line(&mut self, block_id: &str, text: &str, depth: usize) {
+        self.push_rendered(block_id, text, Vec::new(), depth);
+    }
+
+    // expressions は text の中での値ブロックの範囲。字下げしたあとの位置に直して記録する
+    fn push_rendered(&mut self, block_id: &str, text: &str, expressions: Vec<SourceSpan>, depth: usize) {
+        let lead = text.len() - text.trim_start_matches('\n').len();
+        let text = text.trim_matches('\n');
+        if text.is_empty() {
+            return;
+        }
+        if !self.code.is_empty() {
+            self.code.push('\n');
+        }
+        let start = self.code.len();
+        // text の各行の先頭が code のどこに置かれたか
+        let mut line_starts = Vec::new();
+        let mut offset = 0;
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.code.push('\n');
+            }
+            if !line.is_empty() {
+                self.code.push_str(&INDENT.repeat(depth));
+            }
+            line_starts.push((offset, self.code.len()));
+            self.code.push_str(line);
+            offset += line.len() + 1;
+        }
+        self.spans.push(SourceSpan {
+            start,
+            end: self.code.len(),
+            block_id: block_id.to_string(),
+        });
+        let position = |at: usize| {
+            let at = at.saturating_sub(lead).min(text.len());
+            let (line, code) = line_starts.iter().rev().find(|(line, _)| *line <= at).copied().unwrap_or((0, start));
+            code + at - line
+        };
+        for expression in expressions {
+            self.expressions.push(SourceSpan {
+                start: position(expression.start),
+                end: position(expression.end),
+                block_id: expression.block_id,
+            });
+        }
+    }
+}
+
+fn check_definition(state: &AppState, block: &Block, errors: &mut Vec<GeneratorError>) {
+    match state.definition_status(block) {
+        DefinitionStatus::Resolved => {}
+        _ => errors.push(GeneratorError {
+            block_id: block.id.clone(),
+            message: format!("\"{}\" の定義が見つかりません", block.title),
+        }),
+    }
+}
+
+// 演算子を含む式はスロットに埋め込むときに括弧で囲む
+fn needs_parens(code: &str) -> bool {
+    let mut depth = 0i32;
+    for c in code.trim().chars() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            c if depth == 0 && (c.is_whitespace() || "+-*/%<>=!?:&|,".contains(c)) => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+// はめ込まれた値ブロックがあればその式、なければ入力欄の値。
+// 値ブロックの式の範囲は at (埋め込む位置) からの位置で spans に足す
+fn slot_value(
+    state: &AppState,
+    block: &Block,
+    name: &str,
+    depth: usize,
+    at: usize,
+    errors: &mut Vec<GeneratorError>,
+    spans: &mut Vec<SourceSpan>,
+) -> Option<String> {
+    let reporter = block.content.iter().find(|c| c.id == name).and_then(|c| match &c.content {
+        EnumBlockContent::ContentValue(cv) => cv.reporter.as_ref(),
+        _ => None,
+    });
+    let Some(reporter) = reporter.and_then(|id| state.get_block(id)) else {
+        return block.content_value(name);
+    };
+    if depth >= MAX_NESTING {
+        return Some(String::new());
+    }
+    check_definition(state, reporter, errors);
+    let mut inner = Vec::new();
+    let code = render_block(state, reporter, &reporter.output, depth + 1, errors, &mut inner);
+    let trimmed = code.trim();
+    let lead = code.len() - code.trim_start().len();
+    let parens = needs_parens(&code);
+    let start = at + usize::from(parens);
+    spans.push(SourceSpan {
+        start,
+        end: start + trimmed.len(),
+        block_id: reporter.id.clone(),
+    });
+    spans.extend(inner.into_iter().map(|span| SourceSpan {
+        start: start + span.start.saturating_sub(lead).min(trimmed.len()),
+        end: start + span.end.saturating_sub(lead).min(trimmed.len()),
+        block_id: span.block_id,
+    }));
+    Some(if parens { format!("({})", trimmed) } else { trimmed.to_string() })
+}
+
+fn render_block(
+    state: &AppState,
+    block: &Block,
+    template: &str,
+    depth: usize,
+    errors: &mut Vec<GeneratorError>,
+    spans: &mut Vec<SourceSpan>,
+) -> String {
+    let mut nested = Vec::new();
+    let line = template::render_at(template, |name, at| slot_value(state, block, name, depth, at, &mut nested, spans));
+    errors.append(&mut nested);
+    for name in template::placeholders(&line) {
+        errors.push(GeneratorError {
+            block_id: block.id.clone(),
+            message: format!("\"{}\" のフィールド {{{{{}}}}} が未定義です", block.title, name),
+        });
+    }
+    line
+}
+
+// C ブロックは出力の {{body}} の位置に内側のスタックを字下げして展開する
+fn render_stack(
+    state: &AppState,
+    root: &str,
+    depth: usize,
+    builder: &mut CodeBuilder,
+    errors: &mut Vec<GeneratorError>,
+) {
+    for id in state.stack_ids(root) {
+        let Some(block) = state.get_block(&id) else {
+            continue;
+        };
+        let (head, tail) = match template::split_body(&block.output) {
+            Some((head, tail)) if block.has_body() => (head, Some(tail)),
+            _ => (block.output.as_str(), None),
+        };
+        check_definition(state, block, errors);
+        let mut expressions = Vec::new();
+        let line = render_block(state, block, head, 0, errors, &mut expressions);
+        builder.push_rendered(&block.id, &line, expressions, depth);
+        let Some(tail) = tail else {
+            continue;
+        };
+        match &block.body_id {
+            Some(body) if depth < MAX_NESTING => render_stack(state, body, depth + 1, builder, errors),
+            _ => {}
+        }
+        let mut expressions = Vec::new();
+        let line = render_block(state, block, tail, 0, errors, &mut expressions);
+        builder.push_rendered(&block.id, &line, expressions, depth);
+    }
+}
+
+// 引数の取得ブロックは、そのマイブロックの本体の中でしか使えない
+fn check_params(state: &AppState, root: &str, owner: Option<&str>, errors: &mut Vec<GeneratorError>) {
+    for id in state.tree_ids(root) {
+        let Some(block) = state.get_block(&id) else {
+            continue;
+        };
+        match state.function_of(block) {
+            Some((function, kind)) if kind.starts_with("param") && owner != Some(function.id.as_str()) => {
+                errors.push(GeneratorError {
+                    block_id: block.id.clone(),
+                    message: format!("引数 \"{}\" は「{}」の定義の外では使えません", block.title, function.name),
+                })
+            }
+            _ => {}
+        }
+    }
+}
+
+// Flag から (間接的にも) 呼ばれるマイブロックを集める。
+// 再帰は終了条件があれば正しく動くので、出力したうえで警告にとどめる
+fn visit_function(
+    state: &AppState,
+    function_id: &str,
+    call_id: &str,
+    path: &mut Vec<String>,
+    visited: &mut Vec<String>,
+    errors: &mut Vec<GeneratorError>,
+    warnings: &mut Vec<GeneratorError>,
+) {
+    let Some(function) = state.functions.iter().find(|f| f.id == function_id) else {
+        return;
+    };
+    if let Some(start) = path.iter().position(|id| id == function_id) {
+        let names: Vec<&str> = path[start..]
+            .iter()
+            .chain([&function.id])
+            .filter_map(|id| state.functions.iter().find(|f| &f.id == id))
+            .map(|f| f.name.as_str())
+            .collect();
+        warnings.push(GeneratorError {
+            block_id: call_id.to_string(),
+            message: format!(
+                "「{}」が再帰的に呼び出されています ({})。終了する条件があるか確認してください",
+                function.name,
+                names.join(" → ")
+            ),
+        });
+        return;
+    }
+    if visited.iter().any(|id| id == function_id) {
+        return;
+    }
+    visited.push(function.id.clone());
+    let defines = state.define_blocks(function_id);
+    let Some(hat) = defines.first() else {
+        errors.push(GeneratorError {
+            block_id: call_id.to_string(),
+            message: format!("「{}」の定義ブロックがワークスペースにありません", function.name),
+        });
+        return;
+    };
+    for duplicate in &defines[1..] {
+        errors.push(GeneratorError {
+            block_id: duplicate.id.clone(),
+            message: format!("「{}」の定義ブロックが複数あります", function.name),
+        });
+    }
+    path.push(function.id.clone());
+    for (callee, call_id) in state.called_functions(&hat.id) {
+        visit_function(state, &callee.id, &call_id, path, visited, errors, warnings);
+    }
+    path.pop();
+}
+
+pub fn generate(state: &AppState, flag_id: &str) -> Option<GeneratedCode> {
+    let flag = state.get_block(flag_id).filter(|b| b.block_type == BlockType::Flag)?;
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let mut builder = CodeBuilder::default();
+
+    let stack = state.stack_ids(flag_id);
+    if stack.len() < 2 {
+        errors.push(GeneratorError {
+            block_id: flag.id.clone(),
+            message: "フラグにブロックが接続されていません".to_string(),
+        });
+    }
+    // 使っている変数は先頭でまとめて宣言する
+    for variable in state.used_variables(flag_id) {
+        let declaration = match variable.initial.trim() {
+            "" => format!("var {};", variable.name),
+            initial => format!("var {} = {};", variable.name, initial),
+        };
+        builder.push_line(&flag.id, &declaration, 0);
+    }
+    // 使っているマイブロックは function 宣言にする (使われていないものは出力しない)
+    let mut visited = Vec::new();
+    for (function, call_id) in state.called_functions(flag_id) {
+        visit_function(state, &function.id, &call_id, &mut Vec::new(), &mut visited, &mut errors, &mut warnings);
+    }
+    for function in state.functions.iter().filter(|f| visited.contains(&f.id)) {
+        let Some(hat) = state.define_blocks(&function.id).first().map(|b| b.id.clone()) else {
+            continue;
+        };
+        let params: Vec<&str> = function.params.iter().map(|p| p.name.as_str()).collect();
+        builder.push_line(&hat, &format!("function {}({}) {{", function.name, params.join(", ")), 0);
+        render_stack(state, &hat, 1, &mut builder, &mut errors);
+        builder.push_line(&hat, "}", 0);
+        check_params(state, &hat, Some(&function.id), &mut errors);
+        errors.extend(types::check_stack(state, &hat, &ValueType::Any));
+    }
+    let stack_start = builder.code.len();
+    render_stack(state, flag_id, 0, &mut builder, &mut errors);
+    check_params(state, flag_id, None, &mut errors);
+    errors.extend(types::check_stack(state, flag_id, &state.target_type(flag_id)));
+
+    Some(GeneratedCode {
+        flag_id: flag.id.clone(),
+        flag_title: flag.title.clone(),
+        target: flag.target.clone(),
+        code: builder.code,
+        stack_start,
+        spans: builder.spans,
+        expressions: builder.expressions,
+        errors,
+        warnings,
+    })
+}
+
+// どの Flag からも呼ばれていないマイブロックの定義
+pub fn unused_functions(state: &AppState) -> Vec<GeneratorError> {
+    let mut visited = Vec::new();
+    for flag_id in flag_ids(state) {
+        for (function, call_id) in state.called_functions(&flag_id) {
+            visit_function(state, &function.id, &call_id, &mut Vec::new(), &mut visited, &mut Vec::new(), &mut Vec::new());
+        }
+    }
+    state
+        .functions
+        .iter()
+        .filter(|f| !visited.contains(&f.id))
+        .filter_map(|f| {
+            state.define_blocks(&f.id).first().map(|hat| GeneratorError {
+                block_id: hat.id.clone(),
+                message: format!("「{}」はどの Flag からも使われていません", f.name),
+            })
+        })
+        .collect()
+}
+
+// ブロックへの切り出し用に、スタックの途中からの出力を組み立てる (未解決のフィールドは残す)
+pub fn render_fragment(state: &AppState, root: &str) -> String {
+    let Some(block) = state.get_block(root) else {
+        return String::new();
+    };
+    let mut errors = Vec::new();
+    if block.block_type == BlockType::Value {
+        return render_block(state, block, &block.output, 0, &mut errors, &mut Vec::new());
+    }
+    let mut builder = CodeBuilder::default();
+    render_stack(state, root, 0, &mut builder, &mut errors);
+    builder.code
+}
+
+// フィールドが出力に埋め込まれるときのテキスト (コードの編集で値ブロックを残すかの判定用)
+pub fn slot_text(state: &AppState, block: &Block, name: &str) -> Option<String> {
+    slot_value(state, block, name, 0, 0, &mut Vec::new(), &mut Vec::new())
+}
+
+// 値ブロック単体の式 (クリックしたときの評価用)
+pub fn render_expression(state: &AppState, block_id: &str) -> Result<String, String> {
+    let block = state.get_block(block_id).ok_or_else(|| "ブロックが見つかりません".to_string())?;
+    let mut errors = Vec::new();
+    check_definition(state, block, &mut errors);
+    let code = render_block(state, block, &block.output, 0, &mut errors, &mut Vec::new());
+    match errors.into_iter().next() {
+        Some(error) => Err(error.message),
+        None if code.trim().is_empty() => Err(format!("\"{}\" の出力が空です", block.title)),
+        None => Ok(code),
+    }
+}
+
+pub fn flag_ids(state: &AppState) -> Vec<String> {
+    let mut flags: Vec<&Block> = state
+        .blocks
+        .values()
+        .filter(|b| b.block_type == BlockType::Flag)
+        .collect();
+    flags.sort_by(|a, b| {
+        a.position
+            .y
+            .total_cmp(&b.position.y)
+            .then(a.position.x.total_cmp(&b.position.x))
+    });
+    flags.into_iter().map(|b| b.id.clone()).collect()
+}
@@ -0,0 +1,62 @@
+use crate::utils::js::{self, TokenKind};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HighlightKind {
+    Plain,
+    Keyword,
+    Builtin,
+    String,
+    Number,
+    Comment,
+    Punctuation,
+}
+
+impl HighlightKind {
+    pub fn class(&self) -> &'static str {
+        match self {
+            HighlightKind::Plain => "tok-plain",
+            HighlightKind::Keyword => "tok-keyword",
+            HighlightKind::Builtin => "tok-builtin",
+            HighlightKind::String => "tok-string",
+            HighlightKind::Number => "tok-number",
+            HighlightKind::Comment => "tok-comment",
+            HighlightKind::Punctuation => "tok-punct",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub kind: HighlightKind,
+    pub text: String,
+}
+
+// 複数行にまたがるコメントや文字列は行ごとに分割する
+pub fn highlight_lines(code: &str) -> Vec<Vec<Span>> {
+    let mut lines: Vec<Vec<Span>> = vec![Vec::new()];
+    for token in js::tokenize(code) {
+        let kind = match token.kind {
+            TokenKind::Keyword => HighlightKind::Keyword,
+            TokenKind::Identifier if js::is_ae_global(&token.text) => HighlightKind::Builtin,
+            TokenKind::String => HighlightKind::String,
+            TokenKind::Number => HighlightKind::Number,
+            TokenKind::Comment => HighlightKind::Comment,
+            TokenKind::Punctuation => HighlightKind::Punctuation,
+            _ => HighlightKind::Plain,
+        };
+        for (i, part) in token.text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(Vec::new());
+            }
+            if !part.is_empty() {
+                if let Some(line) = lines.last_mut() {
+                    line.push(Span {
+                        kind,
+                        text: part.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    lines
+}
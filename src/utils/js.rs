@@ -0,0 +1,160 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Whitespace,
+    Comment,
+    Identifier,
+    Keyword,
+    Number,
+    String,
+    Punctuation,
+    Unknown,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    // ソース中のバイト位置
+    pub start: usize,
+}
+
+impl Token {
+    pub fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.kind, TokenKind::Whitespace | TokenKind::Comment)
+    }
+
+    pub fn is_punct(&self, text: &str) -> bool {
+        self.kind == TokenKind::Punctuation && self.text == text
+    }
+
+    pub fn is_keyword(&self, text: &str) -> bool {
+        self.kind == TokenKind::Keyword && self.text == text
+    }
+}
+
+pub const RESERVED_WORDS: &[&str] = &[
+    "break", "case", "catch", "class", "const", "continue", "debugger", "default", "delete", "do",
+    "else", "enum", "export", "extends", "false", "finally", "for", "function", "if", "import", "in",
+    "instanceof", "let", "new", "null", "return", "super", "switch", "this", "throw", "true", "try",
+    "typeof", "var", "void", "while", "with", "yield", "of", "undefined",
+];
+
+pub const AE_GLOBALS: &[&str] = &[
+    "time", "value", "thisComp", "thisLayer", "thisProperty", "comp", "footage", "wiggle", "linear",
+    "ease", "easeIn", "easeOut", "clamp", "loopIn", "loopOut", "random", "gaussRandom", "seedRandom",
+    "noise", "Math", "add", "sub", "mul", "div", "length", "normalize", "dot", "cross", "lookAt",
+    "degreesToRadians", "radiansToDegrees", "timeToFrames", "framesToTime", "valueAtTime",
+    "velocityAtTime", "effect", "index", "key", "numKeys", "nearestKey", "transform", "position",
+    "scale", "rotation", "opacity", "anchorPoint", "inPoint", "outPoint", "startTime", "width",
+    "height", "text", "sourceText", "hsvToRgb", "rgbToHsl", "hslToRgb", "posterizeTime",
+];
+
+const PUNCTUATORS: &[&str] = &[
+    ">>>=", "===", "!==", "**=", ">>>", "...", "<<=", ">>=", "=>", "==", "!=", "<=", ">=", "&&",
+    "||", "??", "?.", "++", "--", "+=", "-=", "*=", "/=", "%=", "**", "<<", ">>", "&=", "|=", "^=",
+];
+
+pub fn is_reserved(word: &str) -> bool {
+    RESERVED_WORDS.contains(&word)
+}
+
+pub fn is_ae_global(word: &str) -> bool {
+    AE_GLOBALS.contains(&word)
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c == '_' || c == '$' || c.is_alphabetic()
+}
+
+fn is_identifier_part(c: char) -> bool {
+    is_identifier_start(c) || c.is_ascii_digit()
+}
+
+pub fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(is_identifier_start) && chars.all(is_identifier_part)
+}
+
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < source.len() {
+        let rest = &source[pos..];
+        let c = rest.chars().next().unwrap_or_default();
+        let (kind, len) = if c.is_whitespace() {
+            let len = rest.find(|ch: char| !ch.is_whitespace()).unwrap_or(rest.len());
+            (TokenKind::Whitespace, len)
+        } else if rest.starts_with("//") {
+            (TokenKind::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            (TokenKind::Comment, body.find("*/").map_or(rest.len(), |end| end + 4))
+        } else if c == '"' || c == '\'' || c == '`' {
+            (TokenKind::String, string_length(rest, c))
+        } else if c.is_ascii_digit() || (c == '.' && rest[1..].starts_with(|ch: char| ch.is_ascii_digit())) {
+            (TokenKind::Number, number_length(rest))
+        } else if is_identifier_start(c) {
+            let len = rest.find(|ch: char| !is_identifier_part(ch)).unwrap_or(rest.len());
+            let kind = if is_reserved(&rest[..len]) { TokenKind::Keyword } else { TokenKind::Identifier };
+            (kind, len)
+        } else if let Some(punct) = PUNCTUATORS.iter().find(|p| rest.starts_with(*p)) {
+            (TokenKind::Punctuation, punct.len())
+        } else if c.is_ascii_punctuation() {
+            (TokenKind::Punctuation, 1)
+        } else {
+            (TokenKind::Unknown, c.len_utf8())
+        };
+
+        tokens.push(Token {
+            kind,
+            text: rest[..len].to_string(),
+            start: pos,
+        });
+        pos += len;
+    }
+    tokens
+}
+
+fn string_length(rest: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (i, c) in rest.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return i + 1;
+        } else if c == '\n' && quote != '`' {
+            return i;
+        }
+    }
+    rest.len()
+}
+
+fn number_length(rest: &str) -> usize {
+    if rest.starts_with("0x") || rest.starts_with("0X") {
+        return 2 + rest[2..].find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(rest.len() - 2);
+    }
+    let mut len = 0;
+    let mut seen_exponent = false;
+    let bytes = rest.as_bytes();
+    while len < bytes.len() {
+        let c = bytes[len] as char;
+        if c.is_ascii_digit() || c == '.' {
+            len += 1;
+        } else if (c == 'e' || c == 'E') && !seen_exponent {
+            seen_exponent = true;
+            len += 1;
+            if len < bytes.len() && (bytes[len] == b'+' || bytes[len] == b'-') {
+                len += 1;
+            }
+        } else {
+            break;
+        }
+    }
+    len
+}
@@ -0,0 +1,358 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+use crate::models::block::{Block, BlockType, EnumBlockContent, ValueType};
+use crate::state::app_state::AppState;
+use crate::utils::generator::{self, GeneratedCode};
+use crate::utils::js::{self, TokenKind};
+use crate::utils::{template, types};
+
+// 上にあるものほど重い
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Severity {
+    pub fn key(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Error => "エラー",
+            Severity::Warning => "警告",
+            Severity::Info => "情報",
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Severity::Error => "✖",
+            Severity::Warning => "⚠",
+            Severity::Info => "ℹ",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    // ブロックに結びつかない問題 (プロジェクトの変数など) は None
+    pub block_id: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, block_id: &str, message: String) -> Self {
+        Self {
+            severity,
+            block_id: Some(block_id.to_string()),
+            message,
+        }
+    }
+}
+
+const TERMINATORS: [&str; 4] = ["return", "break", "continue", "throw"];
+
+fn name_of(block: &Block) -> String {
+    if block.title.is_empty() {
+        format!("{:?}", block.block_type)
+    } else {
+        block.title.clone()
+    }
+}
+
+// 存在しない ID を指す参照と、親子で食い違っている参照
+fn check_links(state: &AppState, diagnostics: &mut Vec<Diagnostic>) {
+    for block in state.blocks.values() {
+        let name = name_of(block);
+        let mut links: Vec<(&str, &String)> = Vec::new();
+        links.extend(block.child_id.as_ref().map(|id| ("次のブロック", id)));
+        links.extend(block.body_id.as_ref().map(|id| ("内側のブロック", id)));
+        links.extend(block.content.iter().filter_map(|c| match &c.content {
+            EnumBlockContent::ContentValue(cv) => cv.reporter.as_ref().map(|id| ("はめ込まれた値ブロック", id)),
+            _ => None,
+        }));
+        for (label, id) in links {
+            match state.get_block(id) {
+                None => diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    &block.id,
+                    format!("\"{}\" の{}が見つかりません", name, label),
+                )),
+                Some(linked) if linked.parent_id.as_ref() != Some(&block.id) => diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    &linked.id,
+                    format!("\"{}\" は \"{}\" につながっていますが、親の記録が食い違っています", name_of(linked), name),
+                )),
+                Some(_) => {}
+            }
+        }
+        let Some(parent_id) = &block.parent_id else {
+            continue;
+        };
+        match state.get_block(parent_id) {
+            None => diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                &block.id,
+                format!("\"{}\" の親ブロックが見つかりません", name),
+            )),
+            Some(parent) => {
+                let referenced = parent.child_id.as_ref() == Some(&block.id)
+                    || parent.body_id.as_ref() == Some(&block.id)
+                    || parent.reporter_slots().any(|(_, id)| id == block.id);
+                if !referenced {
+                    diagnostics.push(Diagnostic::new(
+                        Severity::Error,
+                        &block.id,
+                        format!("\"{}\" の親 \"{}\" からこのブロックへの参照がありません", name, name_of(parent)),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn is_root(state: &AppState, block: &Block) -> bool {
+    block.parent_id.as_ref().is_none_or(|id| state.get_block(id).is_none())
+}
+
+// Flag につながっていないスタックと空の Flag、スタックごとの型の食い違い
+fn check_stacks(state: &AppState, diagnostics: &mut Vec<Diagnostic>) {
+    for root in state.blocks.values().filter(|b| is_root(state, b)) {
+        let expected = match root.block_type {
+            BlockType::Flag => {
+                if root.child_id.is_none() {
+                    diagnostics.push(Diagnostic::new(
+                        Severity::Warning,
+                        &root.id,
+                        "Flag にブロックがつながっていません".to_string(),
+                    ));
+                }
+                state.target_type(&root.id)
+            }
+            BlockType::Define => {
+                if root.child_id.is_none() {
+                    diagnostics.push(Diagnostic::new(
+                        Severity::Info,
+                        &root.id,
+                        format!("\"{}\" の本体が空です", name_of(root)),
+                    ));
+                }
+                ValueType::Any
+            }
+            _ => {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Warning,
+                    &root.id,
+                    format!("\"{}\" から始まるスタックは Flag につながっていないため出力されません", name_of(root)),
+                ));
+                ValueType::Any
+            }
+        };
+        for error in types::check_stack(state, &root.id, &expected) {
+            diagnostics.push(Diagnostic::new(Severity::Error, &error.block_id, error.message));
+        }
+    }
+}
+
+// 出力で使われているのに空のままのフィールド
+fn check_fields(state: &AppState, diagnostics: &mut Vec<Diagnostic>) {
+    for block in state.blocks.values() {
+        let used = template::placeholders(&block.output);
+        for content in &block.content {
+            let EnumBlockContent::ContentValue(cv) = &content.content else {
+                continue;
+            };
+            if cv.reporter.is_some() || !cv.value.trim().is_empty() || !used.contains(&content.id.as_str()) {
+                continue;
+            }
+            let field = if cv.title.is_empty() { &content.id } else { &cv.title };
+            diagnostics.push(Diagnostic::new(
+                Severity::Error,
+                &block.id,
+                format!("\"{}\" の「{}」が空です", name_of(block), field),
+            ));
+        }
+    }
+}
+
+fn terminates(block: &Block) -> bool {
+    let tokens = js::tokenize(&block.output);
+    tokens
+        .iter()
+        .find(|t| !t.is_trivia())
+        .is_some_and(|t| TERMINATORS.iter().any(|k| t.is_keyword(k)))
+}
+
+// return などのあとにつながっているブロック
+fn check_unreachable(state: &AppState, diagnostics: &mut Vec<Diagnostic>) {
+    for block in state.blocks.values().filter(|b| terminates(b)) {
+        let Some(next) = block.child_id.as_ref().and_then(|id| state.get_block(id)) else {
+            continue;
+        };
+        diagnostics.push(Diagnostic::new(
+            Severity::Warning,
+            &next.id,
+            format!("\"{}\" のあとのブロックは実行されません", name_of(block)),
+        ));
+    }
+}
+
+struct Declaration {
+    name: String,
+    is_var: bool,
+    function_scope: usize,
+    block_scope: usize,
+    offset: usize,
+}
+
+// 出力コードの var / let / const 宣言を、関数とブロックのスコープ付きで集める
+fn declarations(code: &str) -> Vec<Declaration> {
+    let tokens = js::tokenize(code);
+    let tokens: Vec<_> = tokens.iter().filter(|t| !t.is_trivia()).collect();
+    let mut declarations = Vec::new();
+    let mut scopes = 0;
+    // (ブロックのスコープ, 関数のスコープ)
+    let mut stack = vec![(0, 0)];
+    let mut pending_function = false;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.is_keyword("function") {
+            pending_function = true;
+        } else if token.is_punct("{") {
+            scopes += 1;
+            let function_scope = if pending_function { scopes } else { stack.last().map_or(0, |s| s.1) };
+            stack.push((scopes, function_scope));
+            pending_function = false;
+        } else if token.is_punct("}") && stack.len() > 1 {
+            stack.pop();
+        } else if ["var", "let", "const"].iter().any(|k| token.is_keyword(k)) {
+            let Some(name) = tokens.get(i + 1).filter(|t| t.kind == TokenKind::Identifier) else {
+                continue;
+            };
+            let &(block_scope, function_scope) = stack.last().unwrap_or(&(0, 0));
+            declarations.push(Declaration {
+                name: name.text.clone(),
+                is_var: token.is_keyword("var"),
+                function_scope,
+                block_scope,
+                offset: token.start,
+            });
+        }
+    }
+    declarations
+}
+
+fn block_at_offset(generated: &GeneratedCode, offset: usize) -> Option<&str> {
+    generated
+        .spans
+        .iter()
+        .find(|span| span.start <= offset && offset < span.end)
+        .map(|span| span.block_id.as_str())
+}
+
+// 同じスコープで二度宣言している変数。let / const の重複は実行時エラーになる
+fn check_declarations(state: &AppState, outputs: &[GeneratedCode], diagnostics: &mut Vec<Diagnostic>) {
+    for (index, variable) in state.variables.iter().enumerate() {
+        if state.variables[..index].iter().any(|v| v.name == variable.name) {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                block_id: None,
+                message: format!("変数 \"{}\" がプロジェクトで重複して定義されています", variable.name),
+            });
+        }
+    }
+    for generated in outputs {
+        let declarations = declarations(&generated.code);
+        for (index, declaration) in declarations.iter().enumerate() {
+            let earlier = declarations[..index].iter().find(|d| {
+                d.name == declaration.name
+                    && if d.is_var && declaration.is_var {
+                        d.function_scope == declaration.function_scope
+                    } else {
+                        d.block_scope == declaration.block_scope
+                    }
+            });
+            let Some(earlier) = earlier else {
+                continue;
+            };
+            let Some(block_id) = block_at_offset(generated, declaration.offset) else {
+                continue;
+            };
+            let severity = if earlier.is_var && declaration.is_var { Severity::Warning } else { Severity::Error };
+            diagnostics.push(Diagnostic::new(
+                severity,
+                block_id,
+                format!("変数 \"{}\" が二重に宣言されています", declaration.name),
+            ));
+        }
+    }
+}
+
+// 再帰呼び出しなど、出力はできるが確認してほしい箇所
+fn check_warnings(outputs: &[GeneratedCode], diagnostics: &mut Vec<Diagnostic>) {
+    for warning in outputs.iter().flat_map(|g| &g.warnings) {
+        diagnostics.push(Diagnostic::new(Severity::Warning, &warning.block_id, warning.message.clone()));
+    }
+}
+
+fn hash_of(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// 診断に関わる部分 (つながり・入力値・定義) だけの要約。ドラッグで位置が変わっても同じ値になる
+pub fn fingerprint(state: &AppState) -> u64 {
+    // プレビューの元の値は診断に使わない (target_type を参照) ので含めない。
+    // HashMap の並びに左右されないよう、ブロックごとの値を足し合わせる
+    let blocks = state.blocks.values().fold(0u64, |sum, block| {
+        let definition = block.definition.as_ref().map(|d| (&d.id, d.version));
+        let links = (&block.parent_id, &block.child_id, &block.body_id);
+        let content = format!("{:?}{:?}{:?}{:?}", block.block_type, block.content, block.output_type, block.target);
+        sum.wrapping_add(hash_of((&block.id, &block.title, &block.output, definition, links, content)))
+    });
+    let lists = state
+        .get_all_block_lists()
+        .iter()
+        .fold(0u64, |sum, list| sum.wrapping_add(hash_of((&list.id, list.version))));
+    hash_of((blocks, lists, format!("{:?}{:?}", state.variables, state.functions)))
+}
+
+pub fn lint(state: &AppState) -> Vec<Diagnostic> {
+    let outputs: Vec<GeneratedCode> = generator::flag_ids(state)
+        .iter()
+        .filter_map(|id| generator::generate(state, id))
+        .collect();
+    let mut diagnostics = Vec::new();
+    check_links(state, &mut diagnostics);
+    check_stacks(state, &mut diagnostics);
+    check_fields(state, &mut diagnostics);
+    check_unreachable(state, &mut diagnostics);
+    check_declarations(state, &outputs, &mut diagnostics);
+    check_warnings(&outputs, &mut diagnostics);
+
+    // 同じ関数の本体が複数の Flag に出力されても一度だけ報告する
+    let mut seen = HashSet::new();
+    diagnostics.retain(|d| seen.insert((d.block_id.clone(), d.message.clone())));
+    let position = |d: &Diagnostic| {
+        d.block_id
+            .as_ref()
+            .and_then(|id| state.get_block(id))
+            .map_or((f64::MIN, f64::MIN), |b| (b.position.y, b.position.x))
+    };
+    diagnostics.sort_by(|a, b| {
+        let (ay, ax) = position(a);
+        let (by, bx) = position(b);
+        a.severity.cmp(&b.severity).then(ay.total_cmp(&by)).then(ax.total_cmp(&bx))
+    });
+    diagnostics
+}
@@ -1 +1,15 @@
-pub mod block;
\ No newline at end of file
+pub mod block;
+pub mod clipboard;
+pub mod debugger;
+pub mod export;
+pub mod generator;
+pub mod highlight;
+pub mod js;
+pub mod lint;
+pub mod parser;
+pub mod preview;
+pub mod sandbox;
+pub mod share;
+pub mod storage;
+pub mod template;
+pub mod types;
\ No newline at end of file
@@ -0,0 +1,624 @@
+use uuid::Uuid;
+
+use crate::models::block::{Block, BlockType, EnumBlockContent, Position};
+use crate::state::app_state::{AppState, BlockList};
+use crate::state::builtin;
+use crate::utils::js::{self, Token, TokenKind};
+use crate::utils::template::{self, Segment};
+
+// 改行をまたいで式が続くかの判定に使う
+const EXPRESSION_END_KEYWORDS: &[&str] = &["this", "true", "false", "null", "undefined", "return", "break", "continue"];
+const CONTINUATION_KEYWORDS: &[&str] = &["else", "catch", "finally", "instanceof", "in"];
+const STATEMENT_PUNCTUATORS: &[&str] = &["{", "}", ";", "++", "--", "!", "~"];
+const BLOCK_KEYWORDS: &[&str] = &["function", "switch", "try", "do", "class", "if", "for", "while"];
+
+struct Node {
+    block: Block,
+    body: Vec<Node>,
+}
+
+enum Pattern {
+    Literal(String),
+    Slot(String),
+}
+
+struct Definition {
+    list: BlockList,
+    pattern: Vec<Pattern>,
+}
+
+struct Parser<'a> {
+    state: &'a AppState,
+    source: &'a str,
+    tokens: Vec<Token>,
+    // 各トークンの直前に改行があるか
+    newline_before: Vec<bool>,
+    // 文と文の間のコメントは Raw ブロックとしてそのまま残す
+    comments: Vec<Token>,
+    next_comment: usize,
+    pos: usize,
+    definitions: Vec<Definition>,
+}
+
+fn ends_expression(token: &Token) -> bool {
+    match token.kind {
+        TokenKind::Identifier | TokenKind::Number | TokenKind::String => true,
+        TokenKind::Keyword => EXPRESSION_END_KEYWORDS.contains(&token.text.as_str()),
+        TokenKind::Punctuation => matches!(token.text.as_str(), ")" | "]" | "}" | "++" | "--"),
+        _ => false,
+    }
+}
+
+fn continues_expression(token: &Token) -> bool {
+    match token.kind {
+        TokenKind::Keyword => CONTINUATION_KEYWORDS.contains(&token.text.as_str()),
+        TokenKind::Punctuation => !STATEMENT_PUNCTUATORS.contains(&token.text.as_str()),
+        _ => false,
+    }
+}
+
+fn bracket_delta(token: &Token) -> i32 {
+    match token.text.as_str() {
+        "(" | "[" | "{" if token.kind == TokenKind::Punctuation => 1,
+        ")" | "]" | "}" if token.kind == TokenKind::Punctuation => -1,
+        _ => 0,
+    }
+}
+
+fn pattern_of(list: &BlockList) -> Option<Vec<Pattern>> {
+    let mut pattern = Vec::new();
+    for segment in template::segments(&list.block.output) {
+        match segment {
+            Segment::Text(text) => pattern.extend(
+                js::tokenize(text)
+                    .into_iter()
+                    .filter(|t| !t.is_trivia())
+                    .map(|t| Pattern::Literal(t.text)),
+            ),
+            Segment::Placeholder(name) => {
+                list.block.content_value(name)?;
+                pattern.push(Pattern::Slot(name.to_string()));
+            }
+        }
+    }
+    if matches!(pattern.last(), Some(Pattern::Literal(text)) if text == ";") {
+        pattern.pop();
+    }
+    // リテラルを含まないテンプレートは何にでも一致してしまう
+    pattern.iter().any(|p| matches!(p, Pattern::Literal(_))).then_some(pattern)
+}
+
+fn literal_count(pattern: &[Pattern]) -> usize {
+    pattern.iter().filter(|p| matches!(p, Pattern::Literal(_))).count()
+}
+
+impl<'a> Parser<'a> {
+    fn new(state: &'a AppState, source: &'a str) -> Self {
+        let mut tokens = Vec::new();
+        let mut newline_before = Vec::new();
+        let mut comments = Vec::new();
+        let mut newline = false;
+        for token in js::tokenize(source) {
+            if token.is_trivia() {
+                newline |= token.text.contains('\n');
+                if token.kind == TokenKind::Comment {
+                    comments.push(token);
+                }
+                continue;
+            }
+            newline_before.push(newline);
+            tokens.push(token);
+            newline = false;
+        }
+
+        let mut definitions: Vec<Definition> = state
+            .get_all_block_lists()
+            .into_iter()
+            .filter(|list| matches!(list.block.block_type, BlockType::Works | BlockType::Move | BlockType::Composition))
+            .filter_map(|list| {
+                pattern_of(list).map(|pattern| Definition {
+                    list: list.clone(),
+                    pattern,
+                })
+            })
+            .collect();
+        // より具体的なテンプレートを優先する
+        definitions.sort_by_key(|d| std::cmp::Reverse(literal_count(&d.pattern)));
+
+        Self {
+            state,
+            source,
+            tokens,
+            newline_before,
+            comments,
+            next_comment: 0,
+            pos: 0,
+            definitions,
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn text(&self, tokens: &[Token]) -> String {
+        match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => self.source[first.start..last.end()].to_string(),
+            _ => String::new(),
+        }
+    }
+
+    // 2 行目以降から 1 行目の字下げを取り除き、往復しても字下げが増えないようにする
+    fn verbatim(&self, tokens: &[Token]) -> String {
+        let text = self.text(tokens);
+        let Some(first) = tokens.first() else {
+            return text;
+        };
+        let line_start = self.source[..first.start].rfind('\n').map_or(0, |i| i + 1);
+        let indent = &self.source[line_start..first.start];
+        if !indent.chars().all(char::is_whitespace) || indent.is_empty() {
+            return text;
+        }
+        text.split('\n')
+            .enumerate()
+            .map(|(i, line)| if i > 0 { line.strip_prefix(indent).unwrap_or(line) } else { line })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn definition(&self, id: &str) -> Option<BlockList> {
+        self.state
+            .get_block_list(id)
+            .cloned()
+            .or_else(|| builtin::control_blocks().into_iter().find(|list| list.id == id))
+    }
+
+    fn instance(&self, id: &str, values: &[(String, String)]) -> Option<Block> {
+        let list = self.definition(id)?;
+        let mut block = list.block.clone();
+        block.definition = Some(list.definition_ref());
+        for (content_id, value) in values {
+            let content = block.content.iter_mut().find(|c| &c.id == content_id)?;
+            match &mut content.content {
+                EnumBlockContent::ContentValue(cv) => cv.value = value.clone(),
+                EnumBlockContent::ContentSelector(cs) if cs.options.iter().any(|o| &o.value == value) => {
+                    cs.value = value.clone();
+                }
+                _ => return None,
+            }
+        }
+        Some(block)
+    }
+
+    fn raw(&self, code: String) -> Node {
+        let block = self
+            .instance(builtin::RAW, &[("code".to_string(), code.clone())])
+            .unwrap_or_else(|| Block {
+                output: code.clone(),
+                ..Block::new(String::new(), BlockType::Raw, code, Position { x: 0.0, y: 0.0 })
+            });
+        Node { block, body: Vec::new() }
+    }
+
+    // offset より前にあるコメント。文の途中にあるものは文のテキストに含まれるので捨てる
+    fn comments_before(&mut self, offset: usize, keep: bool) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        while let Some(comment) = self.comments.get(self.next_comment).filter(|c| c.start < offset) {
+            let text = comment.text.trim_end().to_string();
+            self.next_comment += 1;
+            if keep {
+                nodes.push(self.raw(text));
+            }
+        }
+        nodes
+    }
+
+    fn statements(&mut self, top_level: bool) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        loop {
+            let offset = self.peek().map_or(self.source.len(), |t| t.start);
+            nodes.extend(self.comments_before(offset, true));
+            let Some(token) = self.peek() else {
+                break;
+            };
+            if token.is_punct("}") {
+                if !top_level {
+                    break;
+                }
+                // 対応しない閉じ括弧はそのまま残す
+                self.pos += 1;
+                nodes.push(self.raw("}".to_string()));
+                continue;
+            }
+            nodes.extend(self.statement());
+            if let Some(last) = self.pos.checked_sub(1).map(|i| self.tokens[i].end()) {
+                self.comments_before(last, false);
+            }
+        }
+        nodes
+    }
+
+    fn statement(&mut self) -> Vec<Node> {
+        let Some(token) = self.peek() else {
+            return Vec::new();
+        };
+        if token.is_punct(";") {
+            self.pos += 1;
+            return Vec::new();
+        }
+        if token.is_punct("{") {
+            self.pos += 1;
+            let nodes = self.statements(false);
+            self.eat("}");
+            return nodes;
+        }
+
+        let start = self.pos;
+        let control = if token.is_keyword("if") {
+            self.if_statement()
+        } else if token.is_keyword("for") {
+            self.loop_statement(builtin::FOR, "header")
+        } else if token.is_keyword("while") {
+            self.loop_statement(builtin::WHILE, "condition")
+        } else {
+            None
+        };
+        match control {
+            Some(nodes) => nodes,
+            None => {
+                self.pos = start;
+                vec![self.simple_statement()]
+            }
+        }
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let matched = self.peek().is_some_and(|t| t.is_punct(punct));
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn parenthesized(&mut self) -> Option<String> {
+        if !self.eat("(") {
+            return None;
+        }
+        let start = self.pos;
+        let mut depth = 1;
+        while let Some(token) = self.peek() {
+            depth += bracket_delta(token);
+            if depth == 0 {
+                let text = self.text(&self.tokens[start..self.pos]);
+                self.pos += 1;
+                return Some(text);
+            }
+            self.pos += 1;
+        }
+        None
+    }
+
+    fn body(&mut self) -> Vec<Node> {
+        if self.eat("{") {
+            let nodes = self.statements(false);
+            self.eat("}");
+            nodes
+        } else {
+            self.statement()
+        }
+    }
+
+    fn control(&self, id: &str, values: &[(String, String)], body: Vec<Node>) -> Option<Node> {
+        Some(Node {
+            block: self.instance(id, values)?,
+            body,
+        })
+    }
+
+    fn if_statement(&mut self) -> Option<Vec<Node>> {
+        self.pos += 1;
+        let condition = self.parenthesized()?;
+        let body = self.body();
+        let mut nodes = vec![self.control(builtin::IF, &[("condition".to_string(), condition)], body)?];
+        if self.peek().is_some_and(|t| t.is_keyword("else")) {
+            self.pos += 1;
+            let body = self.body();
+            nodes.push(self.control(builtin::ELSE, &[], body)?);
+        }
+        Some(nodes)
+    }
+
+    fn loop_statement(&mut self, id: &str, content_id: &str) -> Option<Vec<Node>> {
+        self.pos += 1;
+        let header = self.parenthesized()?;
+        let body = self.body();
+        Some(vec![self.control(id, &[(content_id.to_string(), header)], body)?])
+    }
+
+    fn statement_end(&self, start: usize) -> usize {
+        let block_statement = self.tokens[start].kind == TokenKind::Keyword
+            && BLOCK_KEYWORDS.contains(&self.tokens[start].text.as_str());
+        let mut depth = 0;
+        let mut i = start;
+        while let Some(token) = self.tokens.get(i) {
+            if depth == 0 && i > start {
+                if token.is_punct("}") {
+                    return i;
+                }
+                if self.newline_before[i] && ends_expression(&self.tokens[i - 1]) && !continues_expression(token) {
+                    return i;
+                }
+            }
+            if depth == 0 && token.is_punct(";") {
+                return i + 1;
+            }
+            depth += bracket_delta(token);
+            if depth < 0 {
+                return i.max(start + 1);
+            }
+            i += 1;
+            let continues = self
+                .tokens
+                .get(i)
+                .is_some_and(|t| t.kind == TokenKind::Keyword && CONTINUATION_KEYWORDS.contains(&t.text.as_str()));
+            if block_statement && depth == 0 && token.is_punct("}") && !continues {
+                return i;
+            }
+        }
+        i
+    }
+
+    fn simple_statement(&mut self) -> Node {
+        let start = self.pos;
+        let end = self.statement_end(start);
+        self.pos = end;
+        let tokens = &self.tokens[start..end];
+        let expression = match tokens.split_last() {
+            Some((last, rest)) if last.is_punct(";") => rest,
+            _ => tokens,
+        };
+        match self.match_definition(expression) {
+            Some(block) => Node { block, body: Vec::new() },
+            None => self.raw(self.verbatim(tokens)),
+        }
+    }
+
+    fn match_definition(&self, tokens: &[Token]) -> Option<Block> {
+        self.definitions.iter().find_map(|definition| {
+            let mut matcher = Matcher::new(self.source, &definition.pattern, tokens);
+            if !matcher.possible() || !matcher.matches(0, 0) {
+                return None;
+            }
+            self.instance(&definition.list.id, &matcher.captures)
+        })
+    }
+}
+
+// テンプレートとトークン列の照合。スロットの長さは次のリテラルが現れる位置だけを試し、
+// 一度失敗した (パターン位置, トークン位置) は覚えておく
+struct Matcher<'a> {
+    source: &'a str,
+    pattern: &'a [Pattern],
+    tokens: &'a [Token],
+    captures: Vec<(String, String)>,
+    failed: Vec<bool>,
+    // 同じ名前のスロットの値が食い違って諦めた回数。これで失敗した位置は覚えない
+    conflicts: usize,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(source: &'a str, pattern: &'a [Pattern], tokens: &'a [Token]) -> Self {
+        Self {
+            source,
+            pattern,
+            tokens,
+            captures: Vec::new(),
+            failed: vec![false; (pattern.len() + 1) * (tokens.len() + 1)],
+            conflicts: 0,
+        }
+    }
+
+    fn span(&self, start: usize, end: usize) -> &str {
+        &self.source[self.tokens[start].start..self.tokens[end - 1].end()]
+    }
+
+    // リテラルが順に現れ、末尾のリテラルが最後のトークンと一致しなければ照合するまでもない
+    fn possible(&self) -> bool {
+        if let Some(Pattern::Literal(text)) = self.pattern.last() {
+            if self.tokens.last().is_none_or(|t| &t.text != text) {
+                return false;
+            }
+        }
+        let mut tokens = self.tokens.iter();
+        self.pattern.iter().all(|p| match p {
+            Pattern::Literal(text) => tokens.any(|t| &t.text == text),
+            Pattern::Slot(_) => tokens.next().is_some(),
+        })
+    }
+
+    // 各スロットは括弧の釣り合った 1 トークン以上に一致する
+    fn matches(&mut self, pi: usize, ti: usize) -> bool {
+        let Some(first) = self.pattern.get(pi) else {
+            return ti == self.tokens.len();
+        };
+        let key = pi * (self.tokens.len() + 1) + ti;
+        if self.failed[key] {
+            return false;
+        }
+        let conflicts = self.conflicts;
+        let matched = match first {
+            Pattern::Literal(text) => {
+                self.tokens.get(ti).is_some_and(|t| &t.text == text) && self.matches(pi + 1, ti + 1)
+            }
+            Pattern::Slot(name) => self.match_slot(name, pi, ti),
+        };
+        if !matched && self.conflicts == conflicts {
+            self.failed[key] = true;
+        }
+        matched
+    }
+
+    fn match_slot(&mut self, name: &str, pi: usize, ti: usize) -> bool {
+        let anchor = match self.pattern.get(pi + 1) {
+            Some(Pattern::Literal(text)) => Some(text.as_str()),
+            _ => None,
+        };
+        let last = pi + 1 == self.pattern.len();
+        let mut depth = 0;
+        for end in ti + 1..=self.tokens.len() {
+            depth += bracket_delta(&self.tokens[end - 1]);
+            if depth < 0 {
+                return false;
+            }
+            if depth != 0 || (last && end != self.tokens.len()) {
+                continue;
+            }
+            if anchor.is_some_and(|text| self.tokens.get(end).is_none_or(|t| t.text != text)) {
+                continue;
+            }
+            let value = self.span(ti, end);
+            let conflicts = self.captures.iter().any(|(n, v)| n == name && v != value);
+            if conflicts {
+                self.conflicts += 1;
+                continue;
+            }
+            let value = value.to_string();
+            self.captures.push((name.to_string(), value));
+            if self.matches(pi + 1, end) {
+                return true;
+            }
+            self.captures.pop();
+        }
+        false
+    }
+}
+
+fn link(nodes: Vec<Node>, parent_id: Option<String>, blocks: &mut Vec<Block>) -> Option<String> {
+    let ids: Vec<String> = nodes.iter().map(|_| Uuid::new_v4().to_string()).collect();
+    for (i, node) in nodes.into_iter().enumerate() {
+        let mut block = node.block;
+        block.id = ids[i].clone();
+        block.parent_id = if i == 0 { parent_id.clone() } else { Some(ids[i - 1].clone()) };
+        block.child_id = ids.get(i + 1).cloned();
+        block.body_id = link(node.body, Some(block.id.clone()), blocks);
+        blocks.push(block);
+    }
+    ids.first().cloned()
+}
+
+// 式を Flag から始まるスタックに変換する (位置は insert_blocks で決める)
+pub fn parse(state: &AppState, source: &str) -> Vec<Block> {
+    let mut parser = Parser::new(state, source);
+    let mut nodes = Vec::new();
+    if let Some(flag) = parser.instance(builtin::FLAG, &[]) {
+        nodes.push(Node {
+            block: flag,
+            body: Vec::new(),
+        });
+    }
+    nodes.extend(parser.statements(true));
+
+    let mut blocks = Vec::new();
+    link(nodes, None, &mut blocks);
+    blocks
+}
+
+pub fn parse_stack(state: &AppState, source: &str) -> Vec<Block> {
+    let nodes = Parser::new(state, source).statements(true);
+    let mut blocks = Vec::new();
+    link(nodes, None, &mut blocks);
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::block::{BlockContent, Connection, ContentValue};
+    use crate::utils::generator;
+
+    fn field(id: &str) -> BlockContent {
+        BlockContent {
+            id: id.to_string(),
+            content: EnumBlockContent::ContentValue(ContentValue {
+                title: id.to_string(),
+                value: String::new(),
+                placeholder: None,
+                value_type: Default::default(),
+                reporter: None,
+            }),
+        }
+    }
+
+    fn state() -> AppState {
+        let mut state = AppState::default();
+        let flag = Block {
+            connection: Connection::Output,
+            ..Block::new(String::new(), BlockType::Flag, "Flag".to_string(), Position { x: 0.0, y: 0.0 })
+        };
+        state.add_block_list(BlockList::new("builtin", "flag", 1, flag));
+        for list in builtin::control_blocks() {
+            state.add_block_list(list);
+        }
+        let wiggle = Block {
+            connection: Connection::Both,
+            output: "wiggle({{f}}, {{a}}, {{o}}, {{s}});".to_string(),
+            content: ["f", "a", "o", "s"].into_iter().map(field).collect(),
+            ..Block::new(String::new(), BlockType::Works, "wiggle".to_string(), Position { x: 0.0, y: 0.0 })
+        };
+        state.add_block_list(BlockList::new("user", "wiggle", 1, wiggle));
+        state
+    }
+
+    fn round_trip(source: &str) -> String {
+        let mut state = state();
+        state.import_expression(source);
+        let flag = state.selected.clone().unwrap();
+        generator::generate(&state, &flag).unwrap().stack_code().to_string()
+    }
+
+    #[test]
+    fn generated_code_parses_back_to_itself() {
+        let sources = [
+            "x = 1;\ny = x + 2;",
+            "wiggle(2, Math.max(1, 30), 0, [1, 2]);",
+            "if (x > 3) {\n    wiggle(1, 2, 3, 4);\n}\nelse {\n    x = 0;\n}",
+            "for (var i = 0; i < 3; i++) {\n    x += i;\n}",
+        ];
+        for source in sources {
+            let code = round_trip(source);
+            assert_eq!(round_trip(&code), code, "{}", source);
+        }
+    }
+
+    #[test]
+    fn comments_between_statements_are_kept() {
+        let source = "// 準備\nx = 1;\nif (x) {\n    /* 内側 */\n    y = x; // 行末\n}";
+        let code = round_trip(source);
+        assert!(code.contains("// 準備"), "{}", code);
+        assert!(code.contains("/* 内側 */"), "{}", code);
+        assert!(code.contains("// 行末"), "{}", code);
+        assert_eq!(round_trip(&code), code);
+    }
+
+    #[test]
+    fn template_slots_capture_balanced_text() {
+        let state = state();
+        let blocks = parse_stack(&state, "wiggle(f(1, 2), [3, 4], {a: 5}, 6)");
+        assert_eq!(blocks.len(), 1);
+        let values: Vec<String> = ["f", "a", "o", "s"]
+            .iter()
+            .map(|id| blocks[0].content_value(id).unwrap())
+            .collect();
+        assert_eq!(values, ["f(1, 2)", "[3, 4]", "{a: 5}", "6"]);
+    }
+
+    #[test]
+    fn long_lines_that_do_not_match_fall_back_to_raw() {
+        let state = state();
+        let arguments = vec!["1"; 500].join(", ");
+        let source = format!("wiggle({}) + f(x)", arguments);
+        let blocks = parse_stack(&state, &source);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].block_type, BlockType::Raw);
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt;
+
+use boa_engine::{js_string, property::Attribute, Context, JsError, JsString, JsValue, Source};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::models::comp::MockComp;
+
+const PRELUDE: &str = include_str!("ae_prelude.js");
+// 無限ループ対策の上限 (ループごとの反復回数と再帰の深さ)
+const LOOP_ITERATION_LIMIT: u64 = 1_000_000;
+const RECURSION_LIMIT: usize = 400;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PreviewInput {
+    pub time: f64,
+    // プロパティの元の値 (数値または配列)
+    pub value: Value,
+    pub comp: MockComp,
+    // thisLayer として扱うレイヤー名 (なければ既定のスタブ)
+    pub this_layer: Option<String>,
+}
+
+impl Default for PreviewInput {
+    fn default() -> Self {
+        Self {
+            time: 0.0,
+            value: json!(0),
+            comp: MockComp::default(),
+            this_layer: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PreviewError {
+    pub message: String,
+    // 実行時間やメモリなどの上限で中断された
+    #[serde(default)]
+    pub limit: bool,
+}
+
+impl fmt::Display for PreviewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl PreviewError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            limit: false,
+        }
+    }
+
+    pub fn limit(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            limit: true,
+        }
+    }
+}
+
+impl From<JsError> for PreviewError {
+    fn from(error: JsError) -> Self {
+        if error.as_native().is_some_and(|e| e.is_runtime_limit()) {
+            return Self::limit(format!("実行回数の上限を超えたため中断しました: {}", error));
+        }
+        Self::new(error.to_string())
+    }
+}
+
+fn prepare(input: &PreviewInput) -> Result<Context, PreviewError> {
+    let mut context = Context::default();
+    context.runtime_limits_mut().set_loop_iteration_limit(LOOP_ITERATION_LIMIT);
+    context.runtime_limits_mut().set_recursion_limit(RECURSION_LIMIT);
+    let comp = serde_json::to_value(&input.comp).map_err(|e| PreviewError::new(e.to_string()))?;
+    let globals = json!({
+        "time": input.time,
+        "value": input.value,
+        "comp": comp,
+        "thisLayer": input.this_layer,
+    });
+    let globals = JsValue::from_json(&globals, &mut context)?;
+    context.register_global_property(js_string!("__input"), globals, Attribute::all())?;
+    context
+        .eval(Source::from_bytes(PRELUDE))
+        .map_err(|e| PreviewError::new(format!("スタブの初期化に失敗しました: {}", e)))?;
+    Ok(context)
+}
+
+// 最後に評価した式の値を AE と同じく結果とする
+pub fn evaluate(code: &str, input: &PreviewInput) -> Result<Value, PreviewError> {
+    let mut context = prepare(input)?;
+    let result = context.eval(Source::from_bytes(code))?;
+    finish(result, &mut context)
+}
+
+// デバッガ用に __step の記録も返す (途中でエラーになっても記録は残る)
+pub fn evaluate_traced(code: &str, input: &PreviewInput) -> (Result<Value, PreviewError>, Value) {
+    let mut context = match prepare(input) {
+        Ok(context) => context,
+        Err(e) => return (Err(e), Value::Null),
+    };
+    let outcome = context
+        .eval(Source::from_bytes(code))
+        .map_err(PreviewError::from)
+        .and_then(|result| finish(result, &mut context));
+    let trace = context
+        .global_object()
+        .get(js_string!("__trace"), &mut context)
+        .ok()
+        .and_then(|trace| trace.to_json(&mut context).ok())
+        .unwrap_or(Value::Null);
+    (outcome, trace)
+}
+
+fn call_global(context: &mut Context, name: &str, args: &[JsValue]) -> Result<JsValue, PreviewError> {
+    let function = context.global_object().get(JsString::from(name), context)?;
+    match function.as_callable() {
+        Some(function) => Ok(function.call(&JsValue::undefined(), args, context)?),
+        None => Err(PreviewError::new(format!("スタブに {} がありません", name))),
+    }
+}
+
+fn finish(result: JsValue, context: &mut Context) -> Result<Value, PreviewError> {
+    if result.is_undefined() {
+        return Err(PreviewError::new("式の結果が undefined です"));
+    }
+    let result = call_global(context, "__unwrap", &[result])?;
+    Ok(result.to_json(context)?)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    pub time: f64,
+    // 次元ごとの値 (数値なら 1 要素)
+    pub values: Vec<f64>,
+}
+
+// グラフ用にサンプルする最大数 (これを超える場合はフレームを間引く)
+pub const MAX_SAMPLES: usize = 300;
+
+fn to_numbers(value: &Value) -> Option<Vec<f64>> {
+    match value {
+        Value::Number(n) => n.as_f64().map(|v| vec![v]),
+        Value::Bool(b) => Some(vec![if *b { 1.0 } else { 0.0 }]),
+        Value::Array(items) => items.iter().map(|v| v.as_f64()).collect(),
+        _ => None,
+    }
+}
+
+// コンポジションの長さ全体をフレームレートごとに評価する
+pub fn sample(code: &str, input: &PreviewInput) -> Result<Vec<Sample>, PreviewError> {
+    let frames = (input.comp.duration * input.comp.frame_rate).floor().max(0.0) as usize;
+    let step = frames.div_ceil(MAX_SAMPLES).max(1);
+    // 環境の初期化は重いので一度だけ行い、フレームごとに時間を差し替える
+    let mut context = prepare(input)?;
+    let code = JsValue::from(JsString::from(code));
+    let mut samples = Vec::new();
+    for frame in (0..=frames).step_by(step) {
+        let time = frame as f64 / input.comp.frame_rate;
+        let value = call_global(&mut context, "__setTime", &[JsValue::from(time)])
+            .and_then(|_| call_global(&mut context, "__run", std::slice::from_ref(&code)))
+            .and_then(|result| finish(result, &mut context))
+            .map_err(|e| PreviewError {
+                message: format!("{} 秒: {}", format_number(time), e),
+                ..e
+            })?;
+        let values = to_numbers(&value)
+            .ok_or_else(|| PreviewError::new(format!("{} は数値ではないのでグラフにできません", format_value(&value))))?;
+        samples.push(Sample { time, values });
+    }
+    Ok(samples)
+}
+
+// 小数点以下 4 桁までに丸める
+pub fn format_number(value: f64) -> String {
+    let text = format!("{:.4}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+pub fn format_value(value: &Value) -> String {
+    match value {
+        Value::Number(n) => match n.as_f64() {
+            Some(f) => format_number(f),
+            None => n.to_string(),
+        },
+        Value::Array(items) => format!("[{}]", items.iter().map(format_value).collect::<Vec<_>>().join(", ")),
+        Value::String(s) => format!("\"{}\"", s),
+        other => other.to_string(),
+    }
+}
+
+pub fn parse_value(text: &str) -> Option<Value> {
+    serde_json::from_str(text.trim()).ok()
+}
@@ -0,0 +1,297 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::oneshot;
+
+use crate::utils::preview::{self, PreviewError, PreviewInput, Sample};
+
+// 式の評価は UI を止めないよう、同じ実行ファイルを子プロセスとして起動して行う。
+// 子プロセスは使い回し、時間切れや取り消しのときだけ止めて次の評価で起動し直す
+pub const WORKER_ARG: &str = "evaluate-worker";
+
+const EVALUATE_TIMEOUT: Duration = Duration::from_secs(2);
+const SAMPLE_TIMEOUT: Duration = Duration::from_secs(5);
+const MEMORY_LIMIT: usize = 256 * 1024 * 1024;
+// メモリの上限に達して確保に失敗したときに Rust が出すメッセージ
+const ALLOCATION_FAILURE: &str = "memory allocation of";
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+static WORKER: Mutex<Option<Worker>> = Mutex::new(None);
+
+// 子プロセス自身に OS のメモリ上限を掛ける。超えると確保に失敗して異常終了する。
+// mmap で確保した分も数えるようにアドレス空間で制限し、起動時点の大きさに上乗せする
+#[cfg(all(unix, not(target_os = "macos")))]
+fn limit_memory() -> Result<(), String> {
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(0) as usize;
+    let current = std::fs::read_to_string("/proc/self/statm")
+        .ok()
+        .and_then(|statm| statm.split_whitespace().next()?.parse::<usize>().ok())
+        .map_or(0, |pages| pages * page);
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    if unsafe { libc::getrlimit(libc::RLIMIT_AS, &mut limit) } != 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    // 既により厳しい上限があればそのまま使う
+    let wanted = (current + MEMORY_LIMIT) as libc::rlim_t;
+    limit.rlim_cur = wanted.min(limit.rlim_max);
+    limit.rlim_max = limit.rlim_cur;
+    if unsafe { libc::setrlimit(libc::RLIMIT_AS, &limit) } != 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn limit_memory() -> Result<(), String> {
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation, SetInformationJobObject,
+        JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_PROCESS_MEMORY,
+    };
+    use windows_sys::Win32::System::Threading::GetCurrentProcess;
+    unsafe {
+        let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+        if job.is_null() {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+        info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_PROCESS_MEMORY;
+        info.ProcessMemoryLimit = MEMORY_LIMIT;
+        let set = SetInformationJobObject(
+            job,
+            JobObjectExtendedLimitInformation,
+            &info as *const JOBOBJECT_EXTENDED_LIMIT_INFORMATION as *const _,
+            std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+        );
+        if set == 0 || AssignProcessToJobObject(job, GetCurrentProcess()) == 0 {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+    }
+    Ok(())
+}
+
+// macOS はアドレス空間の上限 (RLIMIT_AS) もデータ領域の上限 (RLIMIT_DATA) も mmap での確保に効かない
+#[cfg(not(any(all(unix, not(target_os = "macos")), windows)))]
+fn limit_memory() -> Result<(), String> {
+    Ok(())
+}
+
+const MEMORY_LIMITED: bool = cfg!(any(all(unix, not(target_os = "macos")), windows));
+
+// 設定画面に出す評価の制限の説明
+pub fn limits_description() -> String {
+    let memory = if MEMORY_LIMITED {
+        format!("メモリは {} MB まで使えます。", MEMORY_LIMIT / 1024 / 1024)
+    } else {
+        "この OS ではメモリ使用量を制限できません。大きな配列を作り続ける式には注意してください。".to_string()
+    };
+    format!(
+        "式の評価は {} 秒 (グラフは {} 秒) で打ち切ります。{}",
+        EVALUATE_TIMEOUT.as_secs(),
+        SAMPLE_TIMEOUT.as_secs(),
+        memory
+    )
+}
+
+#[derive(Serialize, Deserialize)]
+enum Job {
+    Evaluate { code: String, input: PreviewInput },
+    Sample { code: String, input: PreviewInput },
+    Trace { code: String, input: PreviewInput },
+}
+
+#[derive(Serialize, Deserialize)]
+enum Reply {
+    Value(Result<Value, PreviewError>),
+    Samples(Result<Vec<Sample>, PreviewError>),
+    Trace(Result<Value, PreviewError>, Value),
+}
+
+// 子プロセス側の入口。標準入力から 1 行ずつ Job を読み、評価して Reply を 1 行で書く
+pub fn run_worker() -> Result<(), Box<dyn std::error::Error>> {
+    // 上限を掛けられなければ評価せずに終了し、親に理由を伝える
+    limit_memory().map_err(|e| format!("メモリの上限を設定できません: {}", e))?;
+    let mut stdout = std::io::stdout().lock();
+    for line in std::io::stdin().lock().lines() {
+        let reply = match serde_json::from_str(&line?)? {
+            Job::Evaluate { code, input } => Reply::Value(preview::evaluate(&code, &input)),
+            Job::Sample { code, input } => Reply::Samples(preview::sample(&code, &input)),
+            Job::Trace { code, input } => {
+                let (outcome, trace) = preview::evaluate_traced(&code, &input);
+                Reply::Trace(outcome, trace)
+            }
+        };
+        serde_json::to_writer(&mut stdout, &reply)?;
+        stdout.write_all(b"\n")?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+struct Worker {
+    child: Child,
+    stdin: ChildStdin,
+    replies: mpsc::Receiver<String>,
+    stderr: thread::JoinHandle<Vec<u8>>,
+}
+
+impl Worker {
+    fn spawn() -> Result<Self, PreviewError> {
+        let program = std::env::current_exe().map_err(|e| PreviewError::new(format!("実行ファイルが見つかりません: {}", e)))?;
+        let mut child = Command::new(program)
+            .arg(WORKER_ARG)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| PreviewError::new(format!("評価プロセスを起動できません: {}", e)))?;
+        let (Some(stdin), Some(stdout), Some(mut stderr)) = (child.stdin.take(), child.stdout.take(), child.stderr.take()) else {
+            let _ = child.kill();
+            return Err(PreviewError::new("評価プロセスと接続できません"));
+        };
+        let (sender, replies) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let stderr = thread::spawn(move || {
+            let mut buffer = Vec::new();
+            let _ = stderr.read_to_end(&mut buffer);
+            buffer
+        });
+        Ok(Self {
+            child,
+            stdin,
+            replies,
+            stderr,
+        })
+    }
+
+    fn stop(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    // 応答の前に終了したときの理由
+    fn failure(mut self) -> PreviewError {
+        let _ = self.child.wait();
+        let stderr = self.stderr.join().unwrap_or_default();
+        let stderr = String::from_utf8_lossy(&stderr);
+        if stderr.contains(ALLOCATION_FAILURE) {
+            return PreviewError::limit(format!(
+                "メモリ使用量が上限 ({} MB) を超えたため中断しました",
+                MEMORY_LIMIT / 1024 / 1024
+            ));
+        }
+        PreviewError::new(format!("評価プロセスが異常終了しました: {}", stderr.trim()))
+    }
+}
+
+fn execute(job: &Job, timeout: Duration, cancel: &AtomicBool) -> Result<Reply, PreviewError> {
+    // 評価は一つずつ行う。待っている間に取り消されたら何もしない
+    let mut slot = WORKER.lock().unwrap_or_else(PoisonError::into_inner);
+    if cancel.load(Ordering::Relaxed) {
+        return Err(PreviewError::new("評価を取り消しました"));
+    }
+    let mut worker = match slot.take() {
+        Some(worker) => worker,
+        None => Worker::spawn()?,
+    };
+    let mut request = serde_json::to_vec(job).map_err(|e| PreviewError::new(e.to_string()))?;
+    request.push(b'\n');
+    if worker.stdin.write_all(&request).and_then(|_| worker.stdin.flush()).is_err() {
+        // 前の評価のあとで終了していたら起動し直す
+        worker.stop();
+        worker = Worker::spawn()?;
+        worker
+            .stdin
+            .write_all(&request)
+            .and_then(|_| worker.stdin.flush())
+            .map_err(|e| PreviewError::new(format!("評価プロセスに送れません: {}", e)))?;
+    }
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match worker.replies.recv_timeout(POLL_INTERVAL) {
+            Ok(line) => {
+                *slot = Some(worker);
+                return serde_json::from_str(&line).map_err(|e| PreviewError::new(format!("評価結果を読み取れません: {}", e)));
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Err(worker.failure()),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
+        let timed_out = Instant::now() >= deadline;
+        if timed_out || cancel.load(Ordering::Relaxed) {
+            worker.stop();
+            return Err(if timed_out {
+                PreviewError::limit(format!("評価が {} 秒以内に終わらなかったため中断しました", timeout.as_secs()))
+            } else {
+                PreviewError::new("評価を取り消しました")
+            });
+        }
+    }
+}
+
+// 受け取る側が破棄されたら子プロセスも止める
+struct Pending {
+    receiver: oneshot::Receiver<Result<Reply, PreviewError>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+fn start(job: Job, timeout: Duration) -> Pending {
+    let (sender, receiver) = oneshot::channel();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let flag = cancel.clone();
+    thread::spawn(move || {
+        let _ = sender.send(execute(&job, timeout, &flag));
+    });
+    Pending { receiver, cancel }
+}
+
+async fn run(job: Job, timeout: Duration) -> Result<Reply, PreviewError> {
+    let mut pending = start(job, timeout);
+    match (&mut pending.receiver).await {
+        Ok(reply) => reply,
+        Err(_) => Err(PreviewError::new("評価プロセスとの通信が切れました")),
+    }
+}
+
+fn unexpected() -> PreviewError {
+    PreviewError::new("評価プロセスから想定外の応答がありました")
+}
+
+pub async fn evaluate(code: String, input: PreviewInput) -> Result<Value, PreviewError> {
+    match run(Job::Evaluate { code, input }, EVALUATE_TIMEOUT).await? {
+        Reply::Value(result) => result,
+        _ => Err(unexpected()),
+    }
+}
+
+pub async fn sample(code: String, input: PreviewInput) -> Result<Vec<Sample>, PreviewError> {
+    match run(Job::Sample { code, input }, SAMPLE_TIMEOUT).await? {
+        Reply::Samples(result) => result,
+        _ => Err(unexpected()),
+    }
+}
+
+pub async fn evaluate_traced(code: String, input: PreviewInput) -> (Result<Value, PreviewError>, Value) {
+    match run(Job::Trace { code, input }, EVALUATE_TIMEOUT).await {
+        Ok(Reply::Trace(outcome, trace)) => (outcome, trace),
+        Ok(_) => (Err(unexpected()), Value::Null),
+        Err(e) => (Err(e), Value::Null),
+    }
+}
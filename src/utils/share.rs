@@ -0,0 +1,160 @@
+use std::fmt;
+use std::io::{Read, Write};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::models::block::Block;
+use crate::state::project::Project;
+
+pub const SHARE_PREFIX: &str = "SCRP-";
+pub const SHARE_FORMAT_VERSION: u8 = 1;
+// version (1) + crc32 (4)
+const HEADER_LEN: usize = 5;
+// 展開後の JSON の上限 (圧縮爆弾対策)
+const MAX_DECODED: u64 = 16 * 1024 * 1024;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum SharePayload {
+    Stack(Vec<Block>),
+    Project(Project),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShareError {
+    InvalidPrefix,
+    InvalidEncoding,
+    UnsupportedVersion(u8),
+    ChecksumMismatch,
+    TooLarge,
+    Corrupted(String),
+}
+
+impl fmt::Display for ShareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShareError::InvalidPrefix => write!(f, "共有コードではありません"),
+            ShareError::InvalidEncoding => write!(f, "共有コードの形式が正しくありません"),
+            ShareError::UnsupportedVersion(version) => {
+                write!(f, "未対応のバージョンです (v{})", version)
+            }
+            ShareError::ChecksumMismatch => write!(f, "共有コードが破損しています"),
+            ShareError::TooLarge => write!(f, "共有コードの中身が大きすぎます"),
+            ShareError::Corrupted(message) => write!(f, "共有コードを読み込めません: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ShareError {}
+
+pub fn encode(payload: &SharePayload) -> Result<String, ShareError> {
+    let json = serde_json::to_vec(payload).map_err(|e| ShareError::Corrupted(e.to_string()))?;
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder
+        .write_all(&json)
+        .map_err(|e| ShareError::Corrupted(e.to_string()))?;
+    let compressed = encoder
+        .finish()
+        .map_err(|e| ShareError::Corrupted(e.to_string()))?;
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + compressed.len());
+    bytes.push(SHARE_FORMAT_VERSION);
+    bytes.extend_from_slice(&crc32fast::hash(&compressed).to_be_bytes());
+    bytes.extend_from_slice(&compressed);
+    Ok(format!("{}{}", SHARE_PREFIX, URL_SAFE_NO_PAD.encode(bytes)))
+}
+
+pub fn decode(code: &str) -> Result<SharePayload, ShareError> {
+    // チャットに貼った際の改行や空白は無視する
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    let body = code.strip_prefix(SHARE_PREFIX).ok_or(ShareError::InvalidPrefix)?;
+    let bytes = URL_SAFE_NO_PAD
+        .decode(body)
+        .map_err(|_| ShareError::InvalidEncoding)?;
+    if bytes.len() < HEADER_LEN {
+        return Err(ShareError::InvalidEncoding);
+    }
+
+    let version = bytes[0];
+    if version != SHARE_FORMAT_VERSION {
+        return Err(ShareError::UnsupportedVersion(version));
+    }
+    let checksum = u32::from_be_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
+    let compressed = &bytes[HEADER_LEN..];
+    if crc32fast::hash(compressed) != checksum {
+        return Err(ShareError::ChecksumMismatch);
+    }
+
+    let mut json = Vec::new();
+    DeflateDecoder::new(compressed)
+        .take(MAX_DECODED + 1)
+        .read_to_end(&mut json)
+        .map_err(|e| ShareError::Corrupted(e.to_string()))?;
+    if json.len() as u64 > MAX_DECODED {
+        return Err(ShareError::TooLarge);
+    }
+    serde_json::from_slice(&json).map_err(|e| ShareError::Corrupted(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::block::{BlockType, Position};
+    use crate::state::project::PROJECT_FORMAT_VERSION;
+
+    fn stack() -> SharePayload {
+        let mut block = Block::new("a".to_string(), BlockType::Works, "wiggle".to_string(), Position { x: 10.0, y: 20.0 });
+        block.output = "wiggle(2, 30);".to_string();
+        SharePayload::Stack(vec![block])
+    }
+
+    // ヘッダーと圧縮データを直接いじって符号化し直す
+    fn tamper(code: &str, edit: impl FnOnce(&mut Vec<u8>)) -> String {
+        let mut bytes = URL_SAFE_NO_PAD.decode(code.strip_prefix(SHARE_PREFIX).unwrap()).unwrap();
+        edit(&mut bytes);
+        format!("{}{}", SHARE_PREFIX, URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    #[test]
+    fn encoded_payload_decodes_back() {
+        let project = SharePayload::Project(Project {
+            format_version: PROJECT_FORMAT_VERSION,
+            blocks: Vec::new(),
+            comp: Default::default(),
+            variables: Vec::new(),
+            functions: Vec::new(),
+        });
+        for payload in [stack(), project] {
+            let code = encode(&payload).unwrap();
+            assert!(code.starts_with(SHARE_PREFIX));
+            assert!(decode(&code).unwrap() == payload);
+        }
+
+        // チャットで折り返された改行は無視する
+        let code = encode(&stack()).unwrap();
+        let (head, tail) = code.split_at(code.len() / 2);
+        assert!(decode(&format!(" {}\n{} ", head, tail)).unwrap() == stack());
+    }
+
+    #[test]
+    fn corrupted_code_is_rejected() {
+        let code = encode(&stack()).unwrap();
+
+        let flipped = tamper(&code, |bytes| *bytes.last_mut().unwrap() ^= 0xff);
+        assert_eq!(decode(&flipped).err(), Some(ShareError::ChecksumMismatch));
+
+        let checksum = tamper(&code, |bytes| bytes[1] ^= 0x01);
+        assert_eq!(decode(&checksum).err(), Some(ShareError::ChecksumMismatch));
+
+        let version = tamper(&code, |bytes| bytes[0] = SHARE_FORMAT_VERSION + 1);
+        assert_eq!(decode(&version).err(), Some(ShareError::UnsupportedVersion(SHARE_FORMAT_VERSION + 1)));
+
+        assert_eq!(decode(&code[SHARE_PREFIX.len()..]).err(), Some(ShareError::InvalidPrefix));
+        assert_eq!(decode(&format!("{}!!", SHARE_PREFIX)).err(), Some(ShareError::InvalidEncoding));
+        assert_eq!(decode(&format!("{}AAA", SHARE_PREFIX)).err(), Some(ShareError::InvalidEncoding));
+    }
+}
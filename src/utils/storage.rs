@@ -0,0 +1,37 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+const APP_DIR: &str = "ScrPression";
+
+pub fn data_dir() -> io::Result<PathBuf> {
+    dirs::data_dir()
+        .map(|dir| dir.join(APP_DIR))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "data directory not found"))
+}
+
+pub fn export_dir() -> io::Result<PathBuf> {
+    let dir = dirs::picture_dir()
+        .or_else(dirs::document_dir)
+        .map(|dir| dir.join(APP_DIR))
+        .map_or_else(|| data_dir().map(|dir| dir.join("exports")), Ok)?;
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+pub fn read_json<T: DeserializeOwned>(file_name: &str) -> io::Result<T> {
+    let path = data_dir()?.join(file_name);
+    let text = fs::read_to_string(path)?;
+    serde_json::from_str(&text).map_err(io::Error::other)
+}
+
+pub fn write_json<T: Serialize>(file_name: &str, value: &T) -> io::Result<()> {
+    let path = data_dir()?.join(file_name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let text = serde_json::to_string_pretty(value).map_err(io::Error::other)?;
+    fs::write(path, text)
+}
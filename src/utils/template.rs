@@ -0,0 +1,84 @@
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+// C ブロックの内側のスタックを埋め込む位置
+pub const BODY_PLACEHOLDER: &str = "body";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+pub fn segments(template: &str) -> Vec<Segment<'_>> {
+    let mut result = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(OPEN) {
+        let after = &rest[start + OPEN.len()..];
+        let Some(end) = after.find(CLOSE) else {
+            break;
+        };
+        if start > 0 {
+            result.push(Segment::Text(&rest[..start]));
+        }
+        result.push(Segment::Placeholder(after[..end].trim()));
+        rest = &after[end + CLOSE.len()..];
+    }
+    if !rest.is_empty() {
+        result.push(Segment::Text(rest));
+    }
+    result
+}
+
+pub fn placeholders(template: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find(OPEN) {
+        let after = &rest[start + OPEN.len()..];
+        match after.find(CLOSE) {
+            Some(end) => {
+                result.push(after[..end].trim());
+                rest = &after[end + CLOSE.len()..];
+            }
+            None => break,
+        }
+    }
+    result
+}
+
+// 未知のプレースホルダはそのまま残す
+pub fn render(template: &str, mut lookup: impl FnMut(&str) -> Option<String>) -> String {
+    render_at(template, |name, _| lookup(name))
+}
+
+// lookup には値を埋め込む位置 (結果の先頭からのバイト数) も渡す
+pub fn render_at(template: &str, mut lookup: impl FnMut(&str, usize) -> Option<String>) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(OPEN) {
+        let after = &rest[start + OPEN.len()..];
+        let Some(end) = after.find(CLOSE) else {
+            break;
+        };
+        result.push_str(&rest[..start]);
+        match lookup(after[..end].trim(), result.len()) {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&rest[start..start + OPEN.len() + end + CLOSE.len()]),
+        }
+        rest = &after[end + CLOSE.len()..];
+    }
+    result.push_str(rest);
+    result
+}
+
+pub fn split_body(template: &str) -> Option<(&str, &str)> {
+    let mut offset = 0;
+    while let Some(start) = template[offset..].find(OPEN).map(|i| offset + i) {
+        let after = start + OPEN.len();
+        let end = after + template[after..].find(CLOSE)?;
+        if template[after..end].trim() == BODY_PLACEHOLDER {
+            return Some((&template[..start], &template[end + CLOSE.len()..]));
+        }
+        offset = end + CLOSE.len();
+    }
+    None
+}
@@ -0,0 +1,143 @@
+use serde_json::Value;
+
+use crate::models::block::{Block, ContentValue, EnumBlockContent, ValueType};
+use crate::state::app_state::AppState;
+use crate::utils::generator::GeneratorError;
+use crate::utils::js::{self, TokenKind};
+use crate::utils::template::{self, Segment};
+
+const MAX_NESTING: usize = 32;
+
+pub fn of_value(value: &Value) -> ValueType {
+    match value {
+        Value::Number(_) => ValueType::Number,
+        Value::Bool(_) => ValueType::Boolean,
+        Value::String(_) => ValueType::String,
+        Value::Array(items) if items.iter().all(Value::is_number) => ValueType::from_dimension(items.len()),
+        _ => ValueType::Any,
+    }
+}
+
+// 入力欄に書かれた式のうち、型が明らかなものだけを判定する
+pub fn infer_literal(text: &str) -> ValueType {
+    let tokens = js::tokenize(text);
+    let tokens: Vec<_> = tokens.iter().filter(|t| !t.is_trivia()).collect();
+    match tokens.as_slice() {
+        [] => ValueType::Any,
+        [token] => match token.kind {
+            TokenKind::Number => ValueType::Number,
+            TokenKind::String => ValueType::String,
+            TokenKind::Keyword if token.text == "true" || token.text == "false" => ValueType::Boolean,
+            TokenKind::Identifier if token.text == "time" => ValueType::Number,
+            TokenKind::Identifier if token.text == "thisLayer" => ValueType::Layer,
+            _ => ValueType::Any,
+        },
+        [sign, number] if (sign.is_punct("-") || sign.is_punct("+")) && number.kind == TokenKind::Number => {
+            ValueType::Number
+        }
+        [open, inner @ .., close] if open.is_punct("[") && close.is_punct("]") => {
+            let mut depth = 0i32;
+            let mut elements = if inner.is_empty() { 0 } else { 1 };
+            for token in inner {
+                match token.text.as_str() {
+                    "(" | "[" | "{" if token.kind == TokenKind::Punctuation => depth += 1,
+                    ")" | "]" | "}" if token.kind == TokenKind::Punctuation => depth -= 1,
+                    "," if depth == 0 => elements += 1,
+                    _ => {}
+                }
+            }
+            ValueType::from_dimension(elements)
+        }
+        _ => ValueType::Any,
+    }
+}
+
+// 出力がフィールド 1 つだけのブロックはその中身の型を返す
+fn passthrough(block: &Block) -> Option<&ContentValue> {
+    let segments = template::segments(block.output.trim());
+    let [Segment::Placeholder(name)] = segments.as_slice() else {
+        return None;
+    };
+    block.content.iter().find(|c| c.id == *name).and_then(|c| match &c.content {
+        EnumBlockContent::ContentValue(cv) => Some(cv),
+        _ => None,
+    })
+}
+
+fn content_type_at(state: &AppState, content: &ContentValue, depth: usize) -> ValueType {
+    match content.reporter.as_ref().and_then(|id| state.get_block(id)) {
+        Some(reporter) if depth < MAX_NESTING => result_type_at(state, reporter, depth + 1),
+        Some(_) => ValueType::Any,
+        None => infer_literal(&content.value),
+    }
+}
+
+fn result_type_at(state: &AppState, block: &Block, depth: usize) -> ValueType {
+    if block.output_type != ValueType::Any {
+        return block.output_type.clone();
+    }
+    passthrough(block).map_or(ValueType::Any, |cv| content_type_at(state, cv, depth))
+}
+
+// スロットに入っている値の型
+pub fn content_type(state: &AppState, content: &ContentValue) -> ValueType {
+    content_type_at(state, content, 0)
+}
+
+// ブロックを評価したときの値の型
+pub fn result_type(state: &AppState, block: &Block) -> ValueType {
+    result_type_at(state, block, 0)
+}
+
+pub fn mismatch_message(slot_title: &str, expected: &ValueType, actual: &ValueType) -> String {
+    format!("「{}」には{}が必要ですが、{}が入っています", slot_title, expected.label(), actual.label())
+}
+
+fn check_block(state: &AppState, block: &Block, depth: usize, errors: &mut Vec<GeneratorError>) {
+    for content in &block.content {
+        let EnumBlockContent::ContentValue(cv) = &content.content else {
+            continue;
+        };
+        let actual = content_type(state, cv);
+        if !cv.value_type.accepts(&actual) {
+            errors.push(GeneratorError {
+                block_id: block.id.clone(),
+                message: mismatch_message(&cv.title, &cv.value_type, &actual),
+            });
+        }
+        let reporter = cv.reporter.as_ref().and_then(|id| state.get_block(id));
+        if let Some(reporter) = reporter.filter(|_| depth < MAX_NESTING) {
+            check_block(state, reporter, depth + 1, errors);
+        }
+    }
+}
+
+// スタック全体のスロットと、最後のブロックの値がプロパティの次元に合うかを調べる
+pub fn check_stack(state: &AppState, flag_id: &str, expected: &ValueType) -> Vec<GeneratorError> {
+    let mut errors = Vec::new();
+    for id in state.tree_ids(flag_id) {
+        if state.is_plugged(&id) {
+            continue;
+        }
+        if let Some(block) = state.get_block(&id) {
+            check_block(state, block, 0, &mut errors);
+        }
+    }
+    let stack = state.stack_ids(flag_id);
+    let last = stack.iter().skip(1).last().and_then(|id| state.get_block(id));
+    if let Some(last) = last {
+        let actual = result_type(state, last);
+        if !expected.accepts(&actual) {
+            errors.push(GeneratorError {
+                block_id: last.id.clone(),
+                message: format!(
+                    "プロパティは{}ですが、最後のブロック \"{}\" は{}を返します",
+                    expected.label(),
+                    last.title,
+                    actual.label()
+                ),
+            });
+        }
+    }
+    errors
+}